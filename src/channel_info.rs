@@ -1,15 +1,157 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use serde::{Deserialize, Serialize};
-use serenity::model::prelude::{GuildId, UserId};
+use serenity::model::{
+    prelude::{ChannelId, GuildId, UserId},
+    Permissions,
+};
+
+use crate::{DB, DBCONNS};
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ChannelInfo {
+    pub channel_id: ChannelId,
     pub guild_id: GuildId,
     pub creator: UserId,
     pub state: ChannelState,
+    /// Unix timestamp in seconds.
+    pub created: u64,
+    /// When the session was last brought back with `/reconnect`, as a unix timestamp in seconds.
+    #[serde(default)]
+    pub reconnected: Option<u64>,
+}
+
+impl ChannelInfo {
+    /// Only the creator of a session and people who can manage channels may end it.
+    pub fn can_stop(&self, user: UserId, permissions: Option<Permissions>) -> bool {
+        self.creator == user || permissions.is_some_and(|p| p.manage_channels())
+    }
+
+    /// One line summary for session lists.
+    pub fn describe(&self) -> String {
+        format!(
+            "<#{}> by <@{}>, started <t:{}:R>",
+            self.channel_id, self.creator, self.created
+        )
+    }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
 pub enum ChannelState {
     Active,
     Archived,
 }
+
+/// How many sessions can be active at once in a server.
+/// A limit of 0 disables it.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct SessionLimits {
+    pub per_user: u64,
+    pub per_guild: u64,
+    /// Archive the user's oldest session to make room for a new one, instead of refusing it.
+    pub archive_oldest: bool,
+}
+
+impl Default for SessionLimits {
+    fn default() -> Self {
+        SessionLimits {
+            per_user: 3,
+            per_guild: 50,
+            archive_oldest: false,
+        }
+    }
+}
+
+impl SessionLimits {
+    /// Summary for the server config panel.
+    pub fn describe(&self) -> String {
+        let limit = |limit: u64| match limit {
+            0 => "unlimited".to_string(),
+            limit => limit.to_string(),
+        };
+        format!(
+            "Per user: {}\nPer server: {}\nWhen full: {}",
+            limit(self.per_user),
+            limit(self.per_guild),
+            match self.archive_oldest {
+                true => "archive the user's oldest session",
+                false => "refuse new sessions",
+            }
+        )
+    }
+}
+
+/// Records a session as active, created by `creator`.
+pub async fn record_active(
+    channel_id: ChannelId,
+    guild_id: GuildId,
+    creator: UserId,
+) -> Result<(), surrealdb::Error> {
+    let info = ChannelInfo {
+        channel_id,
+        guild_id,
+        creator,
+        state: ChannelState::Active,
+        created: now(),
+        reconnected: None,
+    };
+    let _: Option<ChannelInfo> = DB
+        .upsert(("channel_info", channel_id.to_string()))
+        .content(info)
+        .await?;
+    Ok(())
+}
+
+/// Records a session brought back in its old channel, it keeps belonging to whoever created it.
+/// Falls back to a new session by `user` for channels that weren't recorded before.
+pub async fn record_reconnected(
+    channel_id: ChannelId,
+    guild_id: GuildId,
+    user: UserId,
+    previous: Option<ChannelInfo>,
+) -> Result<(), surrealdb::Error> {
+    let Some(previous) = previous else {
+        return record_active(channel_id, guild_id, user).await;
+    };
+    let info = ChannelInfo {
+        state: ChannelState::Active,
+        reconnected: Some(now()),
+        ..previous
+    };
+    let _: Option<ChannelInfo> = DB
+        .upsert(("channel_info", channel_id.to_string()))
+        .content(info)
+        .await?;
+    Ok(())
+}
+
+pub async fn record_archived(channel_id: ChannelId) -> Result<(), surrealdb::Error> {
+    DB.query("UPDATE type::thing('channel_info', $channel) SET state = $state")
+        .bind(("channel", channel_id.to_string()))
+        .bind(("state", ChannelState::Archived))
+        .await?
+        .check()?;
+    Ok(())
+}
+
+/// Sessions of a server that are still running, oldest first.
+/// Sessions recorded as active but lost without being archived, like after a crash, are left out.
+pub async fn active(guild_id: GuildId) -> Result<Vec<ChannelInfo>, surrealdb::Error> {
+    let mut infos: Vec<ChannelInfo> = DB
+        .query("SELECT * OMIT id FROM channel_info WHERE guild_id = $guild AND state = $state ORDER BY created")
+        .bind(("guild", guild_id))
+        .bind(("state", ChannelState::Active))
+        .await?
+        .take(0)?;
+    let conns = DBCONNS.lock().await;
+    infos.retain(|info| conns.contains_key(info.channel_id.as_u64()));
+    Ok(infos)
+}
@@ -0,0 +1,84 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use serenity::model::prelude::ChannelId;
+
+use crate::{persistence::sessions_dir, Conn};
+
+pub const MAX_CHECKPOINTS: usize = 10;
+pub const MAX_NAME_LENGTH: usize = 32;
+
+/// A named snapshot of a session's database, stored on disk as a `.surql` export.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Checkpoint {
+    pub name: String,
+    /// Unix timestamp in seconds.
+    pub created: u64,
+}
+
+fn checkpoint_dir(channel_id: ChannelId) -> PathBuf {
+    sessions_dir()
+        .join("checkpoints")
+        .join(channel_id.to_string())
+}
+
+fn checkpoint_path(channel_id: ChannelId, name: &str) -> PathBuf {
+    checkpoint_dir(channel_id).join(format!("{name}.surql"))
+}
+
+/// Checkpoint names end up in file names, so only allow a safe subset of characters.
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Exports the session's database into a checkpoint, overwriting any checkpoint with the same name.
+#[instrument(skip(conn))]
+pub async fn save(
+    channel_id: ChannelId,
+    conn: &Conn,
+    name: &str,
+) -> Result<Checkpoint, anyhow::Error> {
+    let export = conn.export().await?;
+    tokio::fs::create_dir_all(checkpoint_dir(channel_id)).await?;
+    tokio::fs::write(checkpoint_path(channel_id, name), export).await?;
+    Ok(Checkpoint {
+        name: name.to_string(),
+        created: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    })
+}
+
+pub async fn load(channel_id: ChannelId, name: &str) -> Result<Vec<u8>, anyhow::Error> {
+    Ok(tokio::fs::read(checkpoint_path(channel_id, name)).await?)
+}
+
+pub async fn remove_all(channel_id: ChannelId) {
+    match tokio::fs::remove_dir_all(checkpoint_dir(channel_id)).await {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => error!(error = %err, channel_id = %channel_id, "Failed to remove checkpoints"),
+    }
+}
+
+/// Renders a list of checkpoints for the session panel.
+pub fn describe(checkpoints: &[Checkpoint]) -> Option<String> {
+    if checkpoints.is_empty() {
+        return None;
+    }
+    Some(
+        checkpoints
+            .iter()
+            .map(|c| format!("`{}` <t:{}:R>", c.name, c.created))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
@@ -1,104 +1,158 @@
+// The option helpers hand back `CmdError`s to reply with directly.
+#![allow(clippy::result_large_err)]
+
 use anyhow::anyhow;
+use serde::Serialize;
 use serde_json::Value;
 use serenity::{
     builder::{CreateApplicationCommand, CreateApplicationCommandOption},
     model::prelude::{application_command::CommandDataOption, *},
     prelude::*,
 };
-use surrealdb::opt::auth::{Database, Namespace, Root};
+use surrealdb::{
+    dbs::Session,
+    iam,
+    kvs::Datastore,
+    opt::auth::{Database, Namespace, Record, Root},
+    sql,
+};
 use tokio::time::Instant;
 
 use crate::{
+    components::configurable_session::update_field,
+    identity::{Identity, IDENTITY_FIELD},
     utils::{ephemeral_interaction, user_interaction, CmdError},
     DBCONNS,
 };
 
-/// auth
+/// auth, only changes who the invoking user queries as
 /// - signup
-///     - scope <namespace> <database> <scope> <params>
+///     - record <namespace> <database> <access> <params>
 /// - signin
 ///     - root <username> <password>
 ///     - ns <namespace> <username> <password>
 ///     - db <namespace> <database> <username> <password>
-///     - scope <namespace> <database> <scope> <params>
+///     - record <namespace> <database> <access> <params>
 /// - token <jwt>
-/// - reset (auths as root)
+/// - invalidate (queries as an anonymous guest)
+/// - reset (back to the default root user)
 pub async fn run(
     command: &application_command::ApplicationCommandInteraction,
     ctx: Context,
 ) -> Result<(), anyhow::Error> {
-    let res = match command.guild_id {
-        Some(_) => {
-            let db = match DBCONNS.lock().await.get_mut(command.channel_id.as_u64()) {
-                Some(c) => {
-                    c.last_used = Instant::now();
-                    Ok(c.db.clone())
-                }
-                None => Err(CmdError::NoSession),
-            };
+    let conn = match command.guild_id {
+        Some(_) => match DBCONNS.lock().await.get_mut(command.channel_id.as_u64()) {
+            Some(c) => {
+                c.last_used = Instant::now();
+                Ok(c.clone())
+            }
+            None => Err(CmdError::NoSession),
+        },
+        None => Err(CmdError::NoGuild),
+    };
+    let conn = match conn {
+        Ok(conn) => conn,
+        Err(err) => return err.reply(&ctx, command).await,
+    };
+    let kvs = conn.db.kvs();
+    let user = command.user.id;
+    // Everyone signs in on a session of their own, nobody else's queries are affected.
+    let mut session = Session::default();
 
-            match (command.data.options.first(), db) {
-                (Some(CommandDataOption { name, options, .. }), Ok(db)) => match name.as_str() {
-                    "signup" => match options.first() {
-                        Some(CommandDataOption { name, options, .. }) => match name.as_str() {
-                            "scope" => match scope_options(options) {
-                                Ok(scope) => Ok(db.signup(scope).await.map(|_| "SCOPE")),
-                                Err(err) => Err(err),
-                            },
-                            _ => Err(CmdError::InvalidSubCommand(name.to_string())),
-                        },
-                        None => Err(CmdError::NoSubCommand),
+    let res = match command.data.options.first() {
+        Some(CommandDataOption { name, options, .. }) => match name.as_str() {
+            "signup" => match options.first() {
+                Some(CommandDataOption { name, options, .. }) => match name.as_str() {
+                    "record" => match record_options(options) {
+                        Ok(record) => Ok(signup(kvs, &mut session, record.credentials())
+                            .await
+                            .map(|jwt| Some(record.identity(jwt)))),
+                        Err(err) => Err(err),
                     },
-                    "signin" => match options.first() {
-                        Some(CommandDataOption { name, options, .. }) => match name.as_str() {
-                            "scope" => match scope_options(options) {
-                                Ok(scope) => Ok(db.signin(scope).await.map(|_| "SCOPE")),
-                                Err(err) => Err(err),
-                            },
-                            "db" => match database_options(options) {
-                                Ok(scope) => Ok(db.signin(scope).await.map(|_| "DB")),
-                                Err(err) => Err(err),
-                            },
-                            "ns" => match namespace_options(options) {
-                                Ok(scope) => Ok(db.signin(scope).await.map(|_| "NS")),
-                                Err(err) => Err(err),
-                            },
-                            "root" => match root_options(options) {
-                                Ok(scope) => Ok(db.signin(scope).await.map(|_| "ROOT")),
-                                Err(err) => Err(err),
-                            },
-                            _ => Err(CmdError::InvalidSubCommand(name.to_string())),
-                        },
-                        None => Err(CmdError::NoSubCommand),
+                    _ => Err(CmdError::InvalidSubCommand(name.to_string())),
+                },
+                None => Err(CmdError::NoSubCommand),
+            },
+            "signin" => match options.first() {
+                Some(CommandDataOption { name, options, .. }) => match name.as_str() {
+                    "record" => match record_options(options) {
+                        Ok(record) => Ok(signin(kvs, &mut session, record.credentials())
+                            .await
+                            .map(|jwt| Some(record.identity(jwt)))),
+                        Err(err) => Err(err),
                     },
-                    "token" => match string_argument_by_name(options, "jwt") {
-                        Ok(token) => Ok(db.authenticate(token).await.map(|_| "JWT")),
+                    "db" => match database_options(options) {
+                        Ok(creds) => Ok(signin(kvs, &mut session, creds).await.map(|jwt| {
+                            Some(signed_in(
+                                format!(
+                                    "Database user `{}` on `{}`/`{}`",
+                                    creds.username, creds.namespace, creds.database
+                                ),
+                                jwt,
+                            ))
+                        })),
+                        Err(err) => Err(err),
+                    },
+                    "ns" => match namespace_options(options) {
+                        Ok(creds) => Ok(signin(kvs, &mut session, creds).await.map(|jwt| {
+                            Some(signed_in(
+                                format!(
+                                    "Namespace user `{}` on `{}`",
+                                    creds.username, creds.namespace
+                                ),
+                                jwt,
+                            ))
+                        })),
+                        Err(err) => Err(err),
+                    },
+                    "root" => match root_options(options) {
+                        Ok(creds) => Ok(signin(kvs, &mut session, creds).await.map(|jwt| {
+                            Some(signed_in(format!("Root user `{}`", creds.username), jwt))
+                        })),
                         Err(err) => Err(err),
                     },
-                    "reset" => Ok(db
-                        .signin(Root {
-                            username: "root",
-                            password: "root",
-                        })
-                        .await
-                        .map(|_| "ROOT")),
                     _ => Err(CmdError::InvalidSubCommand(name.to_string())),
                 },
-                (_, Err(e)) => Err(e),
-                (None, _) => Err(CmdError::NoSubCommand),
-            }
-        }
-        None => Err(CmdError::NoGuild),
+                None => Err(CmdError::NoSubCommand),
+            },
+            "token" => match string_argument_by_name(options, "jwt") {
+                Ok(token) => Ok(iam::verify::token(kvs, &mut session, token)
+                    .await
+                    .map_err(surrealdb::Error::from)
+                    .map(|_| {
+                        Some(Identity {
+                            label: "Token passed to `/auth token`".to_string(),
+                            token: Some(token.to_string()),
+                        })
+                    })),
+                Err(err) => Err(err),
+            },
+            "invalidate" => Ok(Ok(Some(Identity {
+                label: "Anonymous guest".to_string(),
+                token: None,
+            }))),
+            "reset" => Ok(Ok(None)),
+            _ => Err(CmdError::InvalidSubCommand(name.to_string())),
+        },
+        None => Err(CmdError::NoSubCommand),
     };
 
     match res {
-        Ok(Ok(new_state)) => {
+        Ok(Ok(new_identity)) => {
+            let description = match &new_identity {
+                Some(identity) => identity.label.clone(),
+                None => "Root user".to_string(),
+            };
+            let identities = conn
+                .set_identity(user, new_identity.map(|identity| (identity, session)))
+                .await;
+            update_field(&ctx, &command.channel_id, IDENTITY_FIELD, Some(identities)).await?;
             user_interaction(
                 &ctx,
                 command,
                 &command.user,
                 "Auth successful",
-                format!("Session auth changed to `{new_state}`, you can now query under that scope.\nAlternatively you can reset it back root by using `/auth reset`."),
+                format!("Your queries in this session now run as: {description}.\nYou can go back to the default root user by using `/auth reset`."),
                 Some(true),
             )
             .await
@@ -109,7 +163,7 @@ pub async fn run(
                 command,
                 "Failed to auth",
                 format!("Auth method errored:\n```rust\n{}\n```", err),
-                Some(false)
+                Some(false),
             )
             .await
         }
@@ -117,12 +171,77 @@ pub async fn run(
     }
 }
 
+fn signed_in(label: String, jwt: String) -> Identity {
+    Identity {
+        label,
+        token: Some(jwt),
+    }
+}
+
+/// Signs `session` in with the same credentials the SDK would send.
+async fn signin(
+    kvs: &Datastore,
+    session: &mut Session,
+    credentials: impl Serialize,
+) -> surrealdb::Result<String> {
+    Ok(iam::signin::signin(kvs, session, object(credentials)?).await?)
+}
+
+async fn signup(
+    kvs: &Datastore,
+    session: &mut Session,
+    credentials: impl Serialize,
+) -> surrealdb::Result<String> {
+    iam::signup::signup(kvs, session, object(credentials)?)
+        .await?
+        .ok_or_else(|| {
+            surrealdb::error::Api::InternalError("No token was issued".to_string()).into()
+        })
+}
+
+fn object(credentials: impl Serialize) -> surrealdb::Result<sql::Object> {
+    let credentials = serde_json::to_value(credentials)
+        .map_err(|err| surrealdb::error::Api::InternalError(err.to_string()))?;
+    match sql::to_value(credentials)? {
+        sql::Value::Object(object) => Ok(object),
+        _ => Ok(sql::Object::default()),
+    }
+}
+
 type AuthHashmap = std::collections::HashMap<String, Value>;
 
-fn scope_options(options: &[CommandDataOption]) -> Result<Scope<'_, AuthHashmap>, CmdError> {
+struct RecordOptions<'a> {
+    namespace: &'a str,
+    database: &'a str,
+    access: &'a str,
+    params: AuthHashmap,
+}
+
+impl<'a> RecordOptions<'a> {
+    fn credentials(&self) -> Record<'a, AuthHashmap> {
+        Record {
+            namespace: self.namespace,
+            database: self.database,
+            access: self.access,
+            params: self.params.clone(),
+        }
+    }
+
+    fn identity(&self, jwt: String) -> Identity {
+        signed_in(
+            format!(
+                "Record user with access `{}` on `{}`/`{}`",
+                self.access, self.namespace, self.database
+            ),
+            jwt,
+        )
+    }
+}
+
+fn record_options(options: &[CommandDataOption]) -> Result<RecordOptions<'_>, CmdError> {
     let namespace = string_argument_by_name(options, "namespace")?;
     let database = string_argument_by_name(options, "database")?;
-    let scope = string_argument_by_name(options, "scope")?;
+    let access = string_argument_by_name(options, "access")?;
     let params = string_argument_by_name(options, "params")?;
     let params = serde_json::from_str::<AuthHashmap>(params).map_err(|e| {
         CmdError::InvalidArgument(
@@ -131,10 +250,10 @@ fn scope_options(options: &[CommandDataOption]) -> Result<Scope<'_, AuthHashmap>
         )
     })?;
 
-    Ok(Scope {
+    Ok(RecordOptions {
         namespace,
         database,
-        scope,
+        access,
         params,
     })
 }
@@ -225,10 +344,10 @@ fn register_signup(
         .name("signup")
         .description("Sign up to a SurrealDB instance")
         .create_sub_option(subcommand! {
-            "scope",  "Sign up to a scope",
+            "record",  "Sign up as a record user",
             string_option!("namespace", "Namespace"),
             string_option!("database", "Database"),
-            string_option!("scope", "Scope"),
+            string_option!("access", "Record access method"),
             string_option!("params", "Additional params (as JSON)")
         })
 }
@@ -259,10 +378,10 @@ fn register_signin(
             string_option!("password", "Password")
         })
         .create_sub_option(subcommand! {
-            "scope", "Sign in to a scope",
+            "record", "Sign in as a record user",
             string_option!("namespace", "Namespace"),
             string_option!("database", "Database"),
-            string_option!("scope", "Scope"),
+            string_option!("access", "Record access method"),
             string_option!("params", "Additional params (as JSON)")
         })
 }
@@ -284,6 +403,12 @@ pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicatio
         .create_option(register_signup)
         .create_option(register_signin)
         .create_option(register_token)
+        .create_option(|option| {
+            option
+                .name("invalidate")
+                .description("Query as an anonymous guest")
+                .kind(command::CommandOptionType::SubCommand)
+        })
         .create_option(|option| {
             option
                 .name("reset")
@@ -0,0 +1,100 @@
+use serenity::model::prelude::application_command::ApplicationCommandInteraction;
+use serenity::model::prelude::command::CommandOptionType;
+
+use serenity::builder::CreateApplicationCommand;
+use serenity::prelude::Context;
+use tokio::time::Instant;
+
+use crate::checkpoints::{self, MAX_CHECKPOINTS};
+use crate::components::configurable_session::{update_field, CHECKPOINTS_FIELD};
+use crate::utils::{ephemeral_interaction, ephemeral_interaction_edit, CmdError};
+use crate::DBCONNS;
+
+pub async fn run(
+    command: &ApplicationCommandInteraction,
+    ctx: Context,
+) -> Result<(), anyhow::Error> {
+    let conn = match DBCONNS.lock().await.get_mut(command.channel_id.as_u64()) {
+        Some(c) => {
+            c.last_used = Instant::now();
+            c.clone()
+        }
+        None => return CmdError::NoSession.reply(&ctx, command).await,
+    };
+
+    let name = match command
+        .data
+        .options
+        .first()
+        .and_then(|o| o.value.as_ref())
+        .and_then(|v| v.as_str())
+    {
+        Some(name) => name.to_string(),
+        None => {
+            return CmdError::ExpectedArgument("a checkpoint name".to_string())
+                .reply(&ctx, command)
+                .await
+        }
+    };
+
+    if !checkpoints::valid_name(&name) {
+        return CmdError::InvalidCheckpointName(name)
+            .reply(&ctx, command)
+            .await;
+    }
+
+    if conn.checkpoints.len() >= MAX_CHECKPOINTS && !conn.checkpoints.iter().any(|c| c.name == name)
+    {
+        return CmdError::TooManyCheckpoints(MAX_CHECKPOINTS)
+            .reply(&ctx, command)
+            .await;
+    }
+
+    ephemeral_interaction(
+        &ctx,
+        command,
+        "Creating checkpoint",
+        "This may take a while",
+        None,
+    )
+    .await?;
+
+    let checkpoint = match checkpoints::save(command.channel_id, &conn, &name).await {
+        Ok(checkpoint) => checkpoint,
+        Err(err) => return CmdError::CheckpointFailed(err).edit(&ctx, command).await,
+    };
+
+    let list = match DBCONNS.lock().await.get_mut(command.channel_id.as_u64()) {
+        Some(c) => {
+            c.checkpoints.retain(|c| c.name != name);
+            c.checkpoints.push(checkpoint);
+            checkpoints::describe(&c.checkpoints)
+        }
+        None => return CmdError::NoSession.edit(&ctx, command).await,
+    };
+    if let Err(err) = update_field(&ctx, &command.channel_id, CHECKPOINTS_FIELD, list).await {
+        warn!(error = %err, "Failed to update session panel");
+    }
+
+    ephemeral_interaction_edit(
+        &ctx,
+        command,
+        "Checkpoint created",
+        format!("Checkpoint `{name}` was saved.\nYou can restore it at any time with `/rollback {name}`."),
+        Some(true),
+    )
+    .await
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("checkpoint")
+        .description("Save the current state of this session's database under a name")
+        .create_option(|option| {
+            option
+                .name("name")
+                .description("Name of the checkpoint, reusing a name overwrites it")
+                .kind(CommandOptionType::String)
+                .required(true)
+        })
+}
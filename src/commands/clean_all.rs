@@ -1,39 +1,49 @@
 use serenity::model::prelude::application_command::ApplicationCommandInteraction;
-use serenity::model::prelude::*;
+use serenity::model::prelude::command::CommandOptionType;
 use serenity::model::Permissions;
 
 use serenity::builder::CreateApplicationCommand;
 
 use serenity::prelude::*;
-use tracing::Instrument;
 
-use crate::utils::clean_channel;
+use crate::maintenance::{self, Scope};
 use crate::utils::ephemeral_interaction;
 use crate::utils::CmdError;
-use crate::DBCONNS;
 
 pub async fn run(
     command: &ApplicationCommandInteraction,
     ctx: Context,
 ) -> Result<(), anyhow::Error> {
-    let map = (*DBCONNS.lock().await).clone();
-    for id in map.keys() {
-        let channel = match ChannelId(*id).to_channel(&ctx).await.unwrap() {
-            Channel::Guild(c) => c,
-            _ => {
-                CmdError::NoGuild.reply(&ctx, command).await?;
-                return Ok(());
+    let global = command
+        .data
+        .options
+        .iter()
+        .find(|o| o.name == "global")
+        .and_then(|o| o.value.as_ref())
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    let scope = match (global, command.guild_id) {
+        (true, _) => {
+            if !maintenance::is_owner(&ctx, command.user.id).await? {
+                return CmdError::NotBotOwner.reply(&ctx, command).await;
             }
-        };
-        let (channel, ctx) = (channel.clone(), ctx.clone());
-        tokio::spawn(async move { clean_channel(channel, &ctx).await }.in_current_span());
-    }
+            Scope::Global
+        }
+        (false, Some(guild_id)) => Scope::Guild(guild_id),
+        (false, None) => return CmdError::NoGuild.reply(&ctx, command).await,
+    };
+
+    let count = maintenance::clean_all(ctx.http.clone(), scope).await;
 
     ephemeral_interaction(
         &ctx,
         command,
         "Cleaned all channels",
-        "All channels should now be cleaned",
+        match scope {
+            Scope::Global => format!("All {count} sessions of every server should now be cleaned"),
+            Scope::Guild(_) => format!("All {count} sessions of this server should now be cleaned"),
+        },
         Some(true),
     )
     .await
@@ -42,6 +52,15 @@ pub async fn run(
 pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
     command
         .name("clean_all")
-        .description("Cleans all channels, this should only be used before a bot is shutdown!")
+        .description("Cleans all sessions in this server")
         .default_member_permissions(Permissions::MANAGE_CHANNELS)
+        .create_option(|option| {
+            option
+                .name("global")
+                .description(
+                    "Clean the sessions of every server instead, only for the bot's owners",
+                )
+                .kind(CommandOptionType::Boolean)
+                .required(false)
+        })
 }
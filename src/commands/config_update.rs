@@ -34,6 +34,12 @@ pub async fn run(
     let changes: ConfigBuilder = ConfigBuilder::build(command);
     config.merge(changes);
 
+    if let Err(err) = config.capabilities.build() {
+        return CmdError::InvalidCapabilities(err)
+            .reply(&ctx, command)
+            .await;
+    }
+
     debug!(config = ?config, "edited config");
 
     let updated: Result<Option<Config>, surrealdb::Error> = DB
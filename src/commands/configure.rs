@@ -33,6 +33,12 @@ pub async fn run(
         None => return CmdError::BuildConfig.reply(&ctx, command).await,
     };
 
+    if let Err(err) = config.capabilities.build() {
+        return CmdError::InvalidCapabilities(err)
+            .reply(&ctx, command)
+            .await;
+    }
+
     debug!(config = ?config, "created config struct");
 
     let created: Result<Option<Config>, surrealdb::Error> = DB
@@ -21,9 +21,13 @@ pub async fn run(
                 "require_query" => {
                     conn.require_query = option.value.clone().unwrap().as_bool().unwrap()
                 }
+                "undo" => conn.undo = option.value.clone().unwrap().as_bool().unwrap(),
                 _ => {}
             }
         }
+        if !conn.undo {
+            conn.forget_undo().await;
+        }
 
         ephemeral_interaction(
             &ctx,
@@ -67,4 +71,12 @@ pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicatio
                 .default_option(false)
                 .required(false)
         })
+        .create_option(|option| {
+            option
+                .name("undo")
+                .description("Whether queries sent as messages that write data can be undone")
+                .kind(CommandOptionType::Boolean)
+                .default_option(false)
+                .required(false)
+        })
 }
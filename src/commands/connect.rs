@@ -6,14 +6,14 @@ use serenity::model::prelude::application_command::ApplicationCommandInteraction
 use serenity::model::prelude::command::CommandOptionType;
 use serenity::model::prelude::{AttachmentType, GuildChannel};
 use serenity::prelude::Context;
-use surrealdb::engine::local::Db;
-use surrealdb::Surreal;
 use tracing::Instrument;
 
 use crate::components::configurable_session::show;
 use crate::{premade, utils::*, DBCONNS};
 
 use crate::config::Config;
+use crate::location::Location;
+use crate::session_db::Handle;
 use crate::DB;
 
 pub async fn run(
@@ -59,17 +59,25 @@ pub async fn run(
                 }
             }
 
-            let db = create_db_instance(&config).await?;
+            let _room = match make_room(&ctx, id, command.user.id, &config).await {
+                Ok(room) => room,
+                Err(err) => return err.reply(&ctx, command).await,
+            };
+
+            let db = create_db_instance(&config, config.engine, channel.id).await?;
 
             register_db(
                 ctx.clone(),
                 db.clone(),
+                config.engine,
                 channel.clone(),
                 config.clone(),
                 crate::ConnType::ConnectedChannel,
                 true,
+                command.user.id,
             )
             .await?;
+            let db = db.root(&Location::default());
 
             match command.data.options.len().cmp(&1) {
                 Ordering::Greater => {
@@ -118,8 +126,14 @@ pub async fn run(
                             }
                         }
                         CommandOptionType::Attachment => {
-                            show(&ctx, &channel, crate::ConnType::ConnectedChannel, &config)
-                                .await?;
+                            show(
+                                &ctx,
+                                &channel,
+                                crate::ConnType::ConnectedChannel,
+                                config.engine,
+                                &config,
+                            )
+                            .await?;
                             load_attachment(op_option, command, ctx, db, channel).await?
                         }
                         opt => {
@@ -131,7 +145,14 @@ pub async fn run(
                     }
                 }
                 Ordering::Less => {
-                    show(&ctx, &channel, crate::ConnType::ConnectedChannel, &config).await?;
+                    show(
+                        &ctx,
+                        &channel,
+                        crate::ConnType::ConnectedChannel,
+                        config.engine,
+                        &config,
+                    )
+                    .await?;
                     ephemeral_interaction(
                         &ctx,
                         command,
@@ -164,7 +185,7 @@ pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicatio
 
 async fn load_premade(
     ctx: Context,
-    db: Surreal<Db>,
+    db: Handle,
     channel: GuildChannel,
     command: &ApplicationCommandInteraction,
     file_name: &'static str,
@@ -173,7 +194,14 @@ async fn load_premade(
     config: &Config,
 ) -> Result<(), anyhow::Error> {
     {
-        show(&ctx, &channel, crate::ConnType::ConnectedChannel, config).await?;
+        show(
+            &ctx,
+            &channel,
+            crate::ConnType::ConnectedChannel,
+            config.engine,
+            config,
+        )
+        .await?;
         ephemeral_interaction(&ctx, command,
             "Premade dataset loading...",
             format!("The dataset is currently being loaded, soon you'll be able to query the {} dataset! \n_Please wait for a confirmation that the dataset is loaded!_", name), None).await?;
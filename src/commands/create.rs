@@ -1,22 +1,19 @@
-use std::cmp::Ordering;
 use std::path::Path;
 
-use memorable_wordlist::kebab_case;
+use serenity::builder::CreateApplicationCommand;
 use serenity::model::prelude::application_command::{
     ApplicationCommandInteraction, CommandDataOptionValue,
 };
 use serenity::model::prelude::command::CommandOptionType;
-use serenity::model::prelude::{AttachmentType, Guild, PermissionOverwrite, UserId};
-use serenity::model::Permissions;
+use serenity::model::prelude::{AttachmentType, GuildChannel};
 use serenity::prelude::Context;
-use serenity::{builder::CreateApplicationCommand, model::prelude::ChannelType};
 use tracing::Instrument;
 
 use crate::components::configurable_session::show;
 use crate::{premade, utils::*};
 
-use crate::config::Config;
-use crate::DB;
+use crate::config::{Config, Engine};
+use crate::{location::Location, session_db::SessionDb, DB, DBCONNS};
 
 pub async fn run(
     command: &ApplicationCommandInteraction,
@@ -35,60 +32,64 @@ pub async fn run(
                 Err(e) => return CmdError::GetConfig(e).reply(&ctx, command).await,
             };
 
-            let guild = Guild::get(&ctx, id).await.unwrap();
+            let engine = match command.data.options.iter().find(|o| o.name == "engine") {
+                Some(option) => match option
+                    .value
+                    .as_ref()
+                    .and_then(|v| v.as_str())
+                    .and_then(Engine::from_option)
+                {
+                    Some(engine) => engine,
+                    None => {
+                        return CmdError::InvalidArgument("engine".to_string(), None)
+                            .reply(&ctx, command)
+                            .await
+                    }
+                },
+                None => config.engine,
+            };
+            let options: Vec<_> = command
+                .data
+                .options
+                .iter()
+                .filter(|o| o.name != "engine")
+                .cloned()
+                .collect();
 
-            let everyone = guild.role_by_name("@everyone");
+            // Checked before the channel and its database instance exist, so there's nothing to clean up.
+            if options.len() > 1 {
+                return CmdError::TooManyArguments(1, options.len())
+                    .reply(&ctx, command)
+                    .await;
+            }
+            if let Some(CommandDataOptionValue::Attachment(attachment)) =
+                options.first().and_then(|o| o.resolved.as_ref())
+            {
+                if let Err(exceeded) = config.quotas.check_attachment(attachment.size) {
+                    return CmdError::QuotaExceeded(exceeded).reply(&ctx, command).await;
+                }
+            }
 
-            let perms = vec![
-                PermissionOverwrite {
-                    allow: Permissions::empty(),
-                    deny: Permissions::VIEW_CHANNEL,
-                    kind: serenity::model::prelude::PermissionOverwriteType::Role(
-                        everyone.unwrap().id,
-                    ),
-                },
-                PermissionOverwrite {
-                    allow: Permissions::VIEW_CHANNEL
-                        .union(Permissions::SEND_MESSAGES)
-                        .union(Permissions::READ_MESSAGE_HISTORY),
-                    deny: Permissions::empty(),
-                    kind: serenity::model::prelude::PermissionOverwriteType::Member(UserId(
-                        *command.application_id.as_u64(),
-                    )),
-                },
-                PermissionOverwrite {
-                    allow: Permissions::VIEW_CHANNEL
-                        .union(Permissions::SEND_MESSAGES)
-                        .union(Permissions::READ_MESSAGE_HISTORY),
-                    deny: Permissions::empty(),
-                    kind: serenity::model::prelude::PermissionOverwriteType::Member(
-                        command.user.id,
-                    ),
-                },
-            ];
+            let _room = match make_room(&ctx, id, command.user.id, &config).await {
+                Ok(room) => room,
+                Err(err) => return err.reply(&ctx, command).await,
+            };
 
-            let channel = guild
-                .create_channel(&ctx, |c| {
-                    c.name(kebab_case(40))
-                        .kind(ChannelType::Text)
-                        .category(config.active_channel)
-                        .permissions(perms)
-                })
-                .await
-                .unwrap();
-            let db = create_db_instance(&config).await?;
+            let channel = create_ephemeral_channel(
+                &ctx,
+                id,
+                &config,
+                command.application_id,
+                command.user.id,
+            )
+            .await?;
+            let db = create_db_instance(&config, engine, channel.id).await?;
 
             let config_clone = config.clone();
 
-            match command.data.options.len().cmp(&1) {
-                Ordering::Greater => {
-                    CmdError::TooManyArguments(1, command.data.options.len())
-                        .reply(&ctx, command)
-                        .await?;
-                    return Ok(());
-                }
-                Ordering::Equal => {
-                    let op_option = command.data.options[0].clone();
+            match options.first() {
+                Some(op_option) => {
+                    let op_option = op_option.clone();
                     match op_option.kind {
                         CommandOptionType::String => {
                             match op_option.value.unwrap().as_str().unwrap() {
@@ -98,11 +99,11 @@ pub async fn run(
                                         format!("You now have your own database instance! Head over to <#{}> while the dataset is currently being loaded.\nOnce you receive a confirmation, you can start to query against the Surreal deal (mini) dataset.", channel.id.as_u64()),
                                         None,
                                     ).await?;
-                                    let db = db.clone();
+                                    let db = db.root(&Location::default());
                                     let (channel, ctx, command) =
                                         (channel.clone(), ctx.clone(), command.clone());
                                     tokio::spawn(async move {
-                                        show(&ctx, &channel, crate::ConnType::EphemeralChannel, &config_clone).await.unwrap();
+                                        show(&ctx, &channel, crate::ConnType::EphemeralChannel, engine, &config_clone).await.unwrap();
                                         db.import("premade/surreal_deal_mini.surql").await.unwrap();
                                         channel.say(&ctx, format!("<@{}> Your instance now has Surreal deal (mini) dataset loaded, try writing some SurrealQL!", command.user.id.as_u64())).await.unwrap();
                                         channel
@@ -123,11 +124,11 @@ pub async fn run(
                                         format!("You now have your own database instance! Head over to <#{}> while the dataset is currently being loaded.\nOnce you receive a confirmation, you can start to query against the Surreal deal dataset.", channel.id.as_u64()),
                                         None,
                                     ).await?;
-                                    let db = db.clone();
+                                    let db = db.root(&Location::default());
                                     let (channel, ctx, command) =
                                         (channel.clone(), ctx.clone(), command.clone());
                                     tokio::spawn(async move {
-                                        show(&ctx, &channel, crate::ConnType::EphemeralChannel, &config_clone).await.unwrap();
+                                        show(&ctx, &channel, crate::ConnType::EphemeralChannel, engine, &config_clone).await.unwrap();
                                         db.import("premade/surreal_deal.surql").await.unwrap();
                                         channel.say(&ctx, format!("<@{}> Your instance now has Surreal deal dataset loaded, try writing some SurrealQL!", command.user.id.as_u64())).await.unwrap();
                                         channel
@@ -144,6 +145,7 @@ pub async fn run(
                                 }
                                 dataset => {
                                     warn!(dataset, "Unknown dataset was requested");
+                                    discard(&ctx, &channel, db, engine).await;
                                     CmdError::UnknownDataset(dataset.to_string())
                                         .reply(&ctx, command)
                                         .await?;
@@ -163,11 +165,11 @@ pub async fn run(
                                 match attachment.download().await {
                                     Ok(data) => {
                                         ephemeral_interaction_edit(&ctx, command, "Attachment downloaded, importing...", "Your attachment has been downloaded and is being imported.", None).await?;
-                                        let db = db.clone();
+                                        let db = db.root(&Location::default());
                                         let (channel, ctx, command) =
                                             (channel.clone(), ctx.clone(), command.clone());
                                         tokio::spawn(async move {
-                                            show(&ctx, &channel, crate::ConnType::EphemeralChannel, &config_clone).await.unwrap();
+                                            show(&ctx, &channel, crate::ConnType::EphemeralChannel, engine, &config_clone).await.unwrap();
                                             if let Err(why) = db
                                                 .query(String::from_utf8_lossy(&data).into_owned())
                                                 .await
@@ -177,17 +179,27 @@ pub async fn run(
                                                 channel.delete(ctx).await.ok();
                                                 return;
                                             }
+                                            let conn = DBCONNS.lock().await.get(channel.id.as_u64()).cloned();
+                                            if let Some(Err(exceeded)) = match &conn {
+                                                Some(conn) => Some(conn.check_usage().await),
+                                                None => None,
+                                            } {
+                                                CmdError::QuotaExceeded(exceeded).edit(&ctx, &command).await.unwrap();
+                                                return;
+                                            }
                                             channel.say(&ctx, format!("<@{}> Your instance now has your dataset, try writing some SurrealQL!", command.user.id.as_u64())).await.unwrap();
                                             ephemeral_interaction_edit(&ctx, &command, "Import completed", format!("Your attachment has been imported, head over to <#{}> to start writing SurrealQL to query your data!.", channel.id.as_u64()), Some(true)).await.unwrap();
                                         }.in_current_span());
                                     }
                                     Err(why) => {
+                                        discard(&ctx, &channel, db, engine).await;
                                         return CmdError::AttachmentDownload(why.into())
                                             .edit(&ctx, command)
-                                            .await
+                                            .await;
                                     }
                                 }
                             } else {
+                                discard(&ctx, &channel, db, engine).await;
                                 return ephemeral_interaction_edit(
                                     &ctx,
                                     command,
@@ -199,6 +211,7 @@ pub async fn run(
                             }
                         }
                         opt => {
+                            discard(&ctx, &channel, db, engine).await;
                             CmdError::UnexpectedArgumentType(opt)
                                 .reply(&ctx, command)
                                 .await?;
@@ -206,11 +219,12 @@ pub async fn run(
                         }
                     }
                 }
-                Ordering::Less => {
+                None => {
                     show(
                         &ctx,
                         &channel,
                         crate::ConnType::EphemeralChannel,
+                        engine,
                         &config_clone,
                     )
                     .await?;
@@ -230,10 +244,12 @@ pub async fn run(
             register_db(
                 ctx,
                 db,
+                engine,
                 channel,
                 config,
                 crate::ConnType::EphemeralChannel,
                 false,
+                command.user.id,
             )
             .await?;
             Ok(())
@@ -242,6 +258,12 @@ pub async fn run(
     }
 }
 
+/// Deletes the channel and database instance of a session that failed to start before being registered.
+async fn discard(ctx: &Context, channel: &GuildChannel, db: SessionDb, engine: Engine) {
+    channel.delete(ctx).await.ok();
+    close_db_instance(db, engine, channel.id).await;
+}
+
 pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
     command
         .name("create")
@@ -254,4 +276,9 @@ pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicatio
                 .kind(CommandOptionType::Attachment)
                 .required(false)
         })
+        .create_option(|option| {
+            Engine::register_option(option)
+                .description("Storage engine for this session, defaults to the server setting")
+                .required(false)
+        })
 }
@@ -27,6 +27,11 @@ pub async fn run(
                 Err(e) => return CmdError::GetConfig(e).reply(&ctx, command).await,
             };
 
+            let _room = match make_room(&ctx, id, command.user.id, &config).await {
+                Ok(room) => room,
+                Err(err) => return err.reply(&ctx, command).await,
+            };
+
             let message = command.data.resolved.messages.keys().next().unwrap();
 
             let channel = command
@@ -34,11 +39,28 @@ pub async fn run(
                 .create_public_thread(&ctx, message, |t| t.name(kebab_case(40)))
                 .await?;
 
-            let db = create_db_instance(&config).await?;
+            let db = create_db_instance(&config, config.engine, channel.id).await?;
 
-            show(&ctx, &channel, crate::ConnType::Thread, &config).await?;
+            show(
+                &ctx,
+                &channel,
+                crate::ConnType::Thread,
+                config.engine,
+                &config,
+            )
+            .await?;
             ephemeral_interaction(&ctx, command, "Thread created!", format!(":information_source: You now have your own database instance! Head over to <#{}> to start writing SurrealQL!", channel.id.as_u64()), Some(true)).await?;
-            register_db(ctx, db, channel, config, crate::ConnType::Thread, true).await?;
+            register_db(
+                ctx,
+                db,
+                config.engine,
+                channel,
+                config,
+                crate::ConnType::Thread,
+                true,
+                command.user.id,
+            )
+            .await?;
             Ok(())
         }
         None => CmdError::NoGuild.reply(&ctx, command).await,
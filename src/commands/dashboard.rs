@@ -0,0 +1,110 @@
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::application_command::ApplicationCommandInteraction;
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::prelude::Context;
+use tokio::time::{Duration, Instant};
+
+use crate::dashboards::{self, Dashboard, MAX_DASHBOARDS, MIN_INTERVAL};
+use crate::utils::{ephemeral_interaction, CmdError};
+use crate::{has_writes, DBCONNS};
+
+pub async fn run(
+    command: &ApplicationCommandInteraction,
+    ctx: Context,
+) -> Result<(), anyhow::Error> {
+    let conn = match DBCONNS.lock().await.get_mut(command.channel_id.as_u64()) {
+        Some(c) => {
+            c.last_used = Instant::now();
+            c.clone()
+        }
+        None => return CmdError::NoSession.reply(&ctx, command).await,
+    };
+
+    let mut query = None;
+    let mut interval = None;
+    for option in &command.data.options {
+        match option.name.as_str() {
+            "query" => query = option.value.as_ref().and_then(|v| v.as_str()),
+            "interval" => interval = option.value.as_ref().and_then(|v| v.as_u64()),
+            _ => {}
+        }
+    }
+    let Some(query) = query else {
+        return CmdError::ExpectedArgument("a query".to_string())
+            .reply(&ctx, command)
+            .await;
+    };
+    let interval = interval.map(|secs| Duration::from_secs(secs).max(MIN_INTERVAL));
+
+    // Dashboards are refreshed as their creator, over and over.
+    if has_writes(query) {
+        return CmdError::WritingDashboard.reply(&ctx, command).await;
+    }
+    if conn.dashboards.lock().await.len() >= MAX_DASHBOARDS {
+        return CmdError::TooManyDashboards(MAX_DASHBOARDS)
+            .reply(&ctx, command)
+            .await;
+    }
+
+    let message_id = dashboards::create(&ctx, command.channel_id, query).await?;
+    let started = dashboards::start(
+        ctx.http.clone(),
+        command.channel_id,
+        &conn,
+        Dashboard {
+            message_id,
+            query: query.to_string(),
+            interval,
+            creator: command.user.id,
+        },
+    )
+    .await;
+    // Other dashboards may have been added while the message was being sent.
+    if !started {
+        command
+            .channel_id
+            .delete_message(&ctx, message_id)
+            .await
+            .ok();
+        return CmdError::TooManyDashboards(MAX_DASHBOARDS)
+            .reply(&ctx, command)
+            .await;
+    }
+
+    ephemeral_interaction(
+        &ctx,
+        command,
+        "Dashboard created",
+        match interval {
+            Some(interval) => format!(
+                "The dashboard will be refreshed every {}.",
+                humantime::format_duration(interval)
+            ),
+            None => "The dashboard will be refreshed whenever the tables it selects from change."
+                .to_string(),
+        },
+        Some(true),
+    )
+    .await
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("dashboard")
+        .description("Pin a query whose result is kept up to date")
+        .create_option(|option| {
+            option
+                .name("query")
+                .description("Query to run for the dashboard")
+                .kind(CommandOptionType::String)
+                .required(true)
+        })
+        .create_option(|option| {
+            option
+                .name("interval")
+                .description("Refresh every this many seconds instead of when the data changes")
+                .kind(CommandOptionType::Integer)
+                .min_int_value(MIN_INTERVAL.as_secs())
+                .required(false)
+        })
+}
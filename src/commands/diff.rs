@@ -0,0 +1,180 @@
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::application_command::{
+    ApplicationCommandInteraction, CommandDataOptionValue,
+};
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::model::prelude::ChannelType;
+use serenity::prelude::Context;
+use surrealdb::dbs::{Capabilities, Session};
+use tokio::time::Instant;
+
+use crate::checkpoints;
+use crate::diff::{diff, snapshot};
+use crate::location::Location;
+use crate::session_db::SessionDb;
+use crate::utils::{ephemeral_interaction, ephemeral_interaction_edit, text_attachment, CmdError};
+use crate::DBCONNS;
+
+pub async fn run(
+    command: &ApplicationCommandInteraction,
+    ctx: Context,
+) -> Result<(), anyhow::Error> {
+    let conn = match DBCONNS.lock().await.get_mut(command.channel_id.as_u64()) {
+        Some(c) => {
+            c.last_used = Instant::now();
+            c.clone()
+        }
+        None => return CmdError::NoSession.reply(&ctx, command).await,
+    };
+
+    if command.data.options.len() != 1 {
+        return CmdError::ExpectedArgument("either a channel or a checkpoint".to_string())
+            .reply(&ctx, command)
+            .await;
+    }
+    let option = &command.data.options[0];
+
+    let (label, other) = match (option.name.as_str(), &option.resolved) {
+        ("channel", Some(CommandDataOptionValue::Channel(channel))) => {
+            // Resolved channels carry the permissions of the user who sent the command.
+            if !channel.permissions.is_some_and(|p| p.view_channel()) {
+                return CmdError::NoSessionIn(channel.id).reply(&ctx, command).await;
+            }
+            let other = DBCONNS.lock().await.get(channel.id.as_u64()).cloned();
+            match other {
+                Some(other) => (format!("<#{}>", channel.id), other.root().await),
+                None => return CmdError::NoSessionIn(channel.id).reply(&ctx, command).await,
+            }
+        }
+        ("checkpoint", Some(CommandDataOptionValue::String(name))) => {
+            if !conn.checkpoints.iter().any(|c| &c.name == name) {
+                return CmdError::UnknownCheckpoint(name.clone())
+                    .reply(&ctx, command)
+                    .await;
+            }
+            ephemeral_interaction(
+                &ctx,
+                command,
+                "Comparing",
+                format!("Loading checkpoint `{name}`, this may take a while."),
+                None,
+            )
+            .await?;
+            let loaded = async {
+                let db = SessionDb::open("memory", Capabilities::default(), None).await?;
+                let db = db.handle(Session::owner()).at("diff", "diff");
+                let export = checkpoints::load(command.channel_id, name).await?;
+                let export = String::from_utf8_lossy(&export).into_owned();
+                // Compare against the namespace and database the session was using back then.
+                let location = Location::from_export(&export);
+                db.query(&export).await?.check()?;
+                Ok::<_, anyhow::Error>(match location {
+                    Some(location) => db.at(&location.namespace, &location.database),
+                    None => db,
+                })
+            }
+            .await;
+            match loaded {
+                Ok(db) => (format!("checkpoint `{name}`"), db),
+                Err(err) => return CmdError::DiffFailed(err).edit(&ctx, command).await,
+            }
+        }
+        _ => {
+            return CmdError::InvalidArgument(option.name.clone(), None)
+                .reply(&ctx, command)
+                .await
+        }
+    };
+
+    if option.name == "channel" {
+        ephemeral_interaction(
+            &ctx,
+            command,
+            "Comparing",
+            format!("Comparing this session with {label}, this may take a while."),
+            None,
+        )
+        .await?;
+    }
+
+    let root = conn.root().await;
+    let (before, after) = match tokio::try_join!(snapshot(&other), snapshot(&root)) {
+        Ok(snapshots) => snapshots,
+        Err(err) => return CmdError::DiffFailed(err).edit(&ctx, command).await,
+    };
+    let diff = diff(&before, &after);
+
+    if diff.is_empty() {
+        return ephemeral_interaction_edit(
+            &ctx,
+            command,
+            "No differences",
+            format!("This session is identical to {label}."),
+            Some(true),
+        )
+        .await;
+    }
+
+    let inline = diff.report.len() < 3800;
+    let (attachment, truncated) = text_attachment(&diff.report, "diff.txt".to_string());
+    command
+        .channel_id
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("Session diff")
+                    .description(if inline {
+                        format!(
+                            "Changes from {label} to this session:\n```diff\n{}\n```",
+                            diff.report
+                        )
+                    } else if truncated {
+                        format!("Changes from {label} to this session are attached.\n:information_source: The report was too long and has been truncated")
+                    } else {
+                        format!("Changes from {label} to this session are attached.")
+                    })
+                    .field("Tables", diff.tables, true)
+                    .field("Fields", diff.fields, true)
+                    .field("Indexes", diff.indexes, true)
+                    .field("Records", diff.records, true)
+                    .author(|a| {
+                        a.name(&command.user.name)
+                            .icon_url(command.user.avatar_url().unwrap_or_default())
+                    })
+            });
+            if !inline {
+                m.add_file(attachment);
+            }
+            m
+        })
+        .await?;
+
+    ephemeral_interaction_edit(
+        &ctx,
+        command,
+        "Diff posted",
+        format!("The differences with {label} have been posted in this channel."),
+        Some(true),
+    )
+    .await
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("diff")
+        .description("Compare this session with another session or a checkpoint")
+        .create_option(|option| {
+            option
+                .name("channel")
+                .description("Channel or thread of the session to compare with")
+                .kind(CommandOptionType::Channel)
+                .channel_types(&[ChannelType::Text, ChannelType::PublicThread])
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("checkpoint")
+                .description("Name of a checkpoint of this session to compare with")
+                .kind(CommandOptionType::String)
+                .required(false)
+        })
+}
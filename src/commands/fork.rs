@@ -0,0 +1,174 @@
+use memorable_wordlist::kebab_case;
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::application_command::ApplicationCommandInteraction;
+use serenity::model::prelude::{ApplicationId, GuildChannel, GuildId};
+use serenity::model::user::User;
+use serenity::prelude::Context;
+use tokio::time::Instant;
+
+use crate::components::configurable_session::{show, update_field};
+use crate::config::Config;
+use crate::db_utils::get_config;
+use crate::location::{Location, LOCATION_FIELD};
+use crate::utils::*;
+use crate::{ConnType, DBCONNS};
+
+pub async fn run(
+    command: &ApplicationCommandInteraction,
+    ctx: Context,
+) -> Result<(), anyhow::Error> {
+    match command.guild_id {
+        Some(guild_id) => {
+            let channel = command.channel_id.to_channel(&ctx).await?.guild().unwrap();
+            fork(
+                &ctx,
+                command,
+                guild_id,
+                command.application_id,
+                &command.user,
+                channel,
+            )
+            .await
+        }
+        None => CmdError::NoGuild.reply(&ctx, command).await,
+    }
+}
+
+/// Copies the session in `source` into a brand new channel, or a new thread for thread sessions.
+pub async fn fork(
+    ctx: &Context,
+    interaction: impl ToInteraction,
+    guild_id: GuildId,
+    application_id: ApplicationId,
+    user: &User,
+    source: GuildChannel,
+) -> Result<(), anyhow::Error> {
+    let conn = match DBCONNS.lock().await.get_mut(source.id.as_u64()) {
+        Some(c) => {
+            c.last_used = Instant::now();
+            c.clone()
+        }
+        None => return CmdError::NoSession.reply(ctx, interaction).await,
+    };
+
+    let config = match get_config(guild_id).await {
+        Ok(Some(config)) => config,
+        Ok(None) => return CmdError::NoConfig.reply(ctx, interaction).await,
+        Err(e) => return CmdError::GetConfig(e).reply(ctx, interaction).await,
+    };
+
+    let _room = match make_room(ctx, guild_id, user.id, &config).await {
+        Ok(room) => room,
+        Err(err) => return err.reply(ctx, interaction).await,
+    };
+
+    ephemeral_interaction(
+        ctx,
+        interaction.clone(),
+        "Forking session",
+        "Copying this session into a new one, this may take a while.",
+        None,
+    )
+    .await?;
+
+    let export = match conn.export().await {
+        Ok(export) => export,
+        Err(err) => return CmdError::ForkFailed(err).edit(ctx, interaction).await,
+    };
+
+    let conn_type = match conn.conn_type {
+        ConnType::Thread => ConnType::Thread,
+        ConnType::ConnectedChannel | ConnType::EphemeralChannel => ConnType::EphemeralChannel,
+    };
+
+    let channel = match conn_type {
+        ConnType::Thread => {
+            let Some(parent) = source.parent_id else {
+                return CmdError::ForkFailed(anyhow::anyhow!("This thread has no parent channel"))
+                    .edit(ctx, interaction)
+                    .await;
+            };
+            let message = parent
+                .say(
+                    ctx,
+                    format!("<@{}> forked the session in <#{}>", user.id, source.id),
+                )
+                .await?;
+            parent
+                .create_public_thread(ctx, message.id, |t| t.name(kebab_case(40)))
+                .await?
+        }
+        _ => create_ephemeral_channel(ctx, guild_id, &config, application_id, user.id).await?,
+    };
+
+    let db = create_db_instance(&config, conn.engine, channel.id).await?;
+    let imported = match db
+        .root(&Location::default())
+        .query(String::from_utf8_lossy(&export))
+        .await
+    {
+        Ok(response) => response.check().map(|_| ()).map_err(Into::into),
+        Err(err) => Err(err.into()),
+    };
+    if let Err(err) = imported {
+        channel.delete(ctx).await.ok();
+        close_db_instance(db, conn.engine, channel.id).await;
+        return CmdError::ForkFailed(err).edit(ctx, interaction).await;
+    }
+
+    let panel_config = Config {
+        pretty: conn.pretty,
+        json: conn.json,
+        ..config.clone()
+    };
+    show(ctx, &channel, conn_type.clone(), conn.engine, &panel_config).await?;
+    let fork = register_db(
+        ctx.clone(),
+        db,
+        conn.engine,
+        channel.clone(),
+        panel_config,
+        conn_type,
+        conn.require_query,
+        user.id,
+    )
+    .await?;
+    if let Some(fork) = DBCONNS.lock().await.get_mut(channel.id.as_u64()) {
+        fork.undo = conn.undo;
+    }
+    let location = conn.location().await;
+    if location != Location::default() {
+        if let Err(err) = fork.use_location(location.clone()).await {
+            warn!(error = %err, "Failed to copy the namespace and database of the session");
+        } else {
+            update_field(ctx, &channel.id, LOCATION_FIELD, Some(location.to_string())).await?;
+        }
+    }
+
+    channel
+        .say(
+            ctx,
+            format!(
+                "<@{}> This session is a copy of <#{}>, try writing some SurrealQL!",
+                user.id, source.id
+            ),
+        )
+        .await?;
+    ephemeral_interaction_edit(
+        ctx,
+        interaction,
+        "Session forked",
+        format!(
+            "Your copy of this session is ready, head over to <#{}> to keep experimenting!",
+            channel.id
+        ),
+        Some(true),
+    )
+    .await
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("fork")
+        .description("Copy this session into a new channel or thread")
+}
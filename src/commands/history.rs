@@ -0,0 +1,18 @@
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::application_command::ApplicationCommandInteraction;
+use serenity::prelude::Context;
+
+use crate::components::history;
+
+pub async fn run(
+    command: &ApplicationCommandInteraction,
+    ctx: Context,
+) -> Result<(), anyhow::Error> {
+    history::show(&ctx, command, command.channel_id).await
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("history")
+        .description("Browse the queries run in this channel and run them again")
+}
@@ -7,12 +7,11 @@ use serenity::model::prelude::{AttachmentType, GuildChannel};
 
 use serenity::builder::CreateApplicationCommand;
 use serenity::prelude::Context;
-use surrealdb::engine::local::Db;
-use surrealdb::Surreal;
 use tokio::time::Instant;
 use tracing::Instrument;
 
 use crate::premade;
+use crate::session_db::Handle;
 
 use crate::utils::{ephemeral_interaction, ephemeral_interaction_edit, load_attachment, CmdError};
 use crate::DBCONNS;
@@ -30,13 +29,15 @@ pub async fn run(
         Some(_guild_id) => {
             let channel = command.channel_id.to_channel(&ctx).await?.guild().unwrap();
 
-            let db = match DBCONNS.lock().await.get_mut(command.channel_id.as_u64()) {
+            let conn = match DBCONNS.lock().await.get_mut(command.channel_id.as_u64()) {
                 Some(c) => {
                     c.last_used = Instant::now();
-                    c.db.clone()
+                    c.clone()
                 }
                 None => return CmdError::NoSession.reply(&ctx, command).await,
             };
+            conn.forget_undo().await;
+            let db = conn.root().await;
 
             match command.data.options.len().cmp(&1) {
                 Ordering::Greater => {
@@ -116,7 +117,7 @@ pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicatio
 
 async fn load_premade(
     ctx: Context,
-    db: Surreal<Db>,
+    db: Handle,
     channel: GuildChannel,
     command: &ApplicationCommandInteraction,
     file_name: &'static str,
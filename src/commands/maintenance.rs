@@ -0,0 +1,155 @@
+use std::time::SystemTime;
+
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::application_command::{
+    ApplicationCommandInteraction, CommandDataOption,
+};
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::model::Permissions;
+use serenity::prelude::Context;
+
+use crate::maintenance::{self, timestamp, Scope, MIN_NOTICE};
+use crate::utils::{ephemeral_interaction, CmdError};
+
+pub async fn run(
+    command: &ApplicationCommandInteraction,
+    ctx: Context,
+) -> Result<(), anyhow::Error> {
+    let Some(CommandDataOption { name, options, .. }) = command.data.options.first() else {
+        return CmdError::NoSubCommand.reply(&ctx, command).await;
+    };
+
+    let global = options
+        .iter()
+        .find(|o| o.name == "global")
+        .and_then(|o| o.value.as_ref())
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let scope = match (global, command.guild_id) {
+        (true, _) => {
+            if !maintenance::is_owner(&ctx, command.user.id).await? {
+                return CmdError::NotBotOwner.reply(&ctx, command).await;
+            }
+            Scope::Global
+        }
+        (false, Some(guild_id)) => Scope::Guild(guild_id),
+        (false, None) => return CmdError::NoGuild.reply(&ctx, command).await,
+    };
+    let affected = match scope {
+        Scope::Global => "every session of every server",
+        Scope::Guild(_) => "every session of this server",
+    };
+
+    match name.as_str() {
+        "at" => {
+            let Some(time) = options
+                .iter()
+                .find(|o| o.name == "time")
+                .and_then(|o| o.value.as_ref())
+                .and_then(|v| v.as_str())
+            else {
+                return CmdError::ExpectedArgument("a time".to_string())
+                    .reply(&ctx, command)
+                    .await;
+            };
+            let at = match parse_time(time) {
+                Ok(at) => at,
+                Err(err) => {
+                    return CmdError::InvalidArgument("time".to_string(), Some(err))
+                        .reply(&ctx, command)
+                        .await
+                }
+            };
+            maintenance::schedule(ctx.http.clone(), scope, at).await;
+            let at = timestamp(at);
+            ephemeral_interaction(
+                &ctx,
+                command,
+                "Maintenance scheduled",
+                format!("Exporting and cleaning {affected} <t:{at}:R>, at <t:{at}:f>.\nThe sessions will be warned an hour, 15 minutes, 5 minutes and a minute before."),
+                Some(true),
+            )
+            .await
+        }
+        "cancel" => match maintenance::cancel(scope).await {
+            Some(at) => {
+                ephemeral_interaction(
+                    &ctx,
+                    command,
+                    "Maintenance cancelled",
+                    format!(
+                        "The maintenance of {affected} planned for <t:{}:f> won't happen.",
+                        timestamp(at)
+                    ),
+                    Some(true),
+                )
+                .await
+            }
+            None => CmdError::NoMaintenance.reply(&ctx, command).await,
+        },
+        _ => CmdError::NoSubCommand.reply(&ctx, command).await,
+    }
+}
+
+/// Accepts either a duration from now, like `30m`, or an RFC 3339 timestamp,
+/// at least [`MIN_NOTICE`] from now so sessions get a warning.
+fn parse_time(time: &str) -> Result<SystemTime, anyhow::Error> {
+    let now = SystemTime::now();
+    let at = match humantime::parse_duration(time) {
+        Ok(duration) => now + duration,
+        Err(_) => humantime::parse_rfc3339_weak(time)?,
+    };
+    if at < now + MIN_NOTICE {
+        anyhow::bail!(
+            "Maintenance has to be at least {} from now",
+            humantime::format_duration(MIN_NOTICE)
+        );
+    }
+    Ok(at)
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("maintenance")
+        .description("Plan a maintenance window that exports and cleans all sessions")
+        .default_member_permissions(Permissions::MANAGE_CHANNELS)
+        .create_option(|option| {
+            option
+                .name("at")
+                .description(
+                    "Export and clean all sessions at a given time, warning them beforehand",
+                )
+                .kind(CommandOptionType::SubCommand)
+                .create_sub_option(|option| {
+                    option
+                        .name("time")
+                        .description("When, e.g. 30m or 2025-01-31T18:00:00Z")
+                        .kind(CommandOptionType::String)
+                        .required(true)
+                })
+                .create_sub_option(|option| {
+                    option
+                        .name("global")
+                        .description(
+                            "Clean the sessions of every server, only for the bot's owners",
+                        )
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("cancel")
+                .description("Cancel the planned maintenance window")
+                .kind(CommandOptionType::SubCommand)
+                .create_sub_option(|option| {
+                    option
+                        .name("global")
+                        .description(
+                            "Cancel the window for every server, only for the bot's owners",
+                        )
+                        .kind(CommandOptionType::Boolean)
+                        .required(false)
+                })
+        })
+}
@@ -1,4 +1,5 @@
-// pub mod auth;
+pub mod auth;
+pub mod checkpoint;
 pub mod clean;
 pub mod clean_all;
 pub mod config_update;
@@ -7,19 +8,30 @@ pub mod configure_channel;
 pub mod connect;
 pub mod create;
 pub mod create_db_thread;
+pub mod dashboard;
+pub mod diff;
 pub mod export;
+pub mod fork;
+pub mod history;
 pub mod load;
+pub mod maintenance;
+pub mod param;
 pub mod q;
 pub mod query;
 pub mod reconnect;
+pub mod rollback;
+pub mod schedule;
+pub mod session;
+pub mod sessions;
 pub mod share;
 pub mod stats;
+pub mod r#use;
 
 use serenity::builder::CreateApplicationCommands;
 
 pub fn register_all(commands: &mut CreateApplicationCommands) -> &mut CreateApplicationCommands {
     commands
-        // .create_application_command(|command| auth::register(command))
+        .create_application_command(|command| auth::register(command))
         .create_application_command(|command| create::register(command))
         .create_application_command(|command| configure::register(command))
         .create_application_command(|command| share::register(command))
@@ -35,4 +47,16 @@ pub fn register_all(commands: &mut CreateApplicationCommands) -> &mut CreateAppl
         .create_application_command(|command| connect::register(command))
         .create_application_command(|command| export::register(command))
         .create_application_command(|command| stats::register(command))
+        .create_application_command(|command| checkpoint::register(command))
+        .create_application_command(|command| rollback::register(command))
+        .create_application_command(|command| fork::register(command))
+        .create_application_command(|command| diff::register(command))
+        .create_application_command(|command| dashboard::register(command))
+        .create_application_command(|command| schedule::register(command))
+        .create_application_command(|command| param::register(command))
+        .create_application_command(|command| r#use::register(command))
+        .create_application_command(|command| session::register(command))
+        .create_application_command(|command| sessions::register(command))
+        .create_application_command(|command| maintenance::register(command))
+        .create_application_command(|command| history::register(command))
 }
@@ -0,0 +1,182 @@
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::application_command::{
+    ApplicationCommandInteraction, CommandDataOption,
+};
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::prelude::Context;
+use tokio::time::Instant;
+
+use crate::components::configurable_session::{update_field, PARAMS_FIELD};
+use crate::params::{self, MAX_PARAMS};
+use crate::utils::{ephemeral_interaction, user_interaction, CmdError};
+use crate::DBCONNS;
+
+pub async fn run(
+    command: &ApplicationCommandInteraction,
+    ctx: Context,
+) -> Result<(), anyhow::Error> {
+    let conn = match DBCONNS.lock().await.get_mut(command.channel_id.as_u64()) {
+        Some(c) => {
+            c.last_used = Instant::now();
+            c.clone()
+        }
+        None => return CmdError::NoSession.reply(&ctx, command).await,
+    };
+
+    let Some(CommandDataOption { name, options, .. }) = command.data.options.first() else {
+        return CmdError::NoSubCommand.reply(&ctx, command).await;
+    };
+
+    let param = options
+        .iter()
+        .find(|o| o.name == "name")
+        .and_then(|o| o.value.as_ref())
+        .and_then(|v| v.as_str())
+        .map(params::normalize_name);
+
+    match name.as_str() {
+        "set" => {
+            let value = options
+                .iter()
+                .find(|o| o.name == "value")
+                .and_then(|o| o.value.as_ref())
+                .and_then(|v| v.as_str());
+            let (Some(param), Some(value)) = (param, value) else {
+                return CmdError::ExpectedArgument("a name and a value".to_string())
+                    .reply(&ctx, command)
+                    .await;
+            };
+            if !params::valid_name(param) {
+                return CmdError::InvalidParamName(param.to_string())
+                    .reply(&ctx, command)
+                    .await;
+            }
+            let value: serde_json::Value = match serde_json::from_str(value) {
+                Ok(value) => value,
+                Err(err) => {
+                    return CmdError::InvalidArgument("value".to_string(), Some(err.into()))
+                        .reply(&ctx, command)
+                        .await
+                }
+            };
+
+            let mut session_params = conn.params.lock().await;
+            if session_params.len() >= MAX_PARAMS && !session_params.contains_key(param) {
+                return CmdError::TooManyParams(MAX_PARAMS)
+                    .reply(&ctx, command)
+                    .await;
+            }
+            session_params.insert(param.to_string(), value.clone());
+            let list = params::describe(&session_params);
+            drop(session_params);
+            // Queries run before and after the change can't be undone as if nothing happened in between.
+            conn.forget_undo().await;
+            if let Err(err) = update_field(&ctx, &command.channel_id, PARAMS_FIELD, list).await {
+                warn!(error = %err, "Failed to update session panel");
+            }
+
+            user_interaction(
+                &ctx,
+                command,
+                &command.user,
+                "Parameter set",
+                format!("Queries in this session can now use `${param}`:\n```json\n{value:#}\n```"),
+                Some(true),
+            )
+            .await
+        }
+        "unset" => {
+            let Some(param) = param else {
+                return CmdError::ExpectedArgument("a parameter name".to_string())
+                    .reply(&ctx, command)
+                    .await;
+            };
+            let mut session_params = conn.params.lock().await;
+            if !session_params.contains_key(param) {
+                return CmdError::UnknownParam(param.to_string())
+                    .reply(&ctx, command)
+                    .await;
+            }
+            session_params.remove(param);
+            let list = params::describe(&session_params);
+            drop(session_params);
+            conn.forget_undo().await;
+            if let Err(err) = update_field(&ctx, &command.channel_id, PARAMS_FIELD, list).await {
+                warn!(error = %err, "Failed to update session panel");
+            }
+
+            user_interaction(
+                &ctx,
+                command,
+                &command.user,
+                "Parameter removed",
+                format!("`${param}` is no longer set in this session."),
+                Some(true),
+            )
+            .await
+        }
+        "list" => {
+            let session_params = conn.params.lock().await;
+            let description = if session_params.is_empty() {
+                "There are no parameters in this session, add one with `/param set`.".to_string()
+            } else {
+                session_params
+                    .iter()
+                    .map(|(name, value)| format!("`${name}`\n```json\n{value:#}\n```"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+            ephemeral_interaction(&ctx, command, "Session parameters", description, None).await
+        }
+        _ => {
+            CmdError::InvalidSubCommand(name.to_string())
+                .reply(&ctx, command)
+                .await
+        }
+    }
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("param")
+        .description("Manage parameters available to every query in this session")
+        .create_option(|option| {
+            option
+                .name("set")
+                .description("Set a parameter, replacing its previous value")
+                .kind(CommandOptionType::SubCommand)
+                .create_sub_option(|option| {
+                    option
+                        .name("name")
+                        .description("Name of the parameter, without the $")
+                        .kind(CommandOptionType::String)
+                        .required(true)
+                })
+                .create_sub_option(|option| {
+                    option
+                        .name("value")
+                        .description("Value of the parameter, as JSON")
+                        .kind(CommandOptionType::String)
+                        .required(true)
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("unset")
+                .description("Remove a parameter")
+                .kind(CommandOptionType::SubCommand)
+                .create_sub_option(|option| {
+                    option
+                        .name("name")
+                        .description("Name of the parameter, without the $")
+                        .kind(CommandOptionType::String)
+                        .required(true)
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("list")
+                .description("List the parameters of this session")
+                .kind(CommandOptionType::SubCommand)
+        })
+}
@@ -4,17 +4,20 @@ use anyhow::anyhow;
 use serenity::{
     builder::CreateApplicationCommand,
     futures::StreamExt,
-    model::prelude::{application_command, Attachment, ChannelId},
+    model::prelude::{application_command, Attachment, ChannelId, GuildId, UserId},
     prelude::Context,
 };
 use tracing::Instrument;
 
 use crate::{
-    components::configurable_session::show,
+    channel_info,
+    components::configurable_session::{show, update_field},
     config::Config,
+    db_utils::get_channel_info,
+    location::LOCATION_FIELD,
     utils::{
-        create_db_instance, ephemeral_interaction, ephemeral_interaction_edit, register_db,
-        CmdError, ToInteraction,
+        create_db_instance, ephemeral_interaction, ephemeral_interaction_edit, make_room,
+        register_db, CmdError, ToInteraction,
     },
     DB, DBCONNS,
 };
@@ -27,6 +30,8 @@ pub async fn run(
         Some(guild_id) => {
             if let Some(_) = DBCONNS.lock().await.get_mut(command.channel_id.as_u64()) {
                 CmdError::ExpectedNoSession.reply(&ctx, command).await
+            } else if !had_session(command.channel_id, guild_id).await {
+                CmdError::NoPastSession.reply(&ctx, command).await
             } else {
                 let result: Result<Option<Config>, surrealdb::Error> =
                     DB.select(("guild_config", guild_id.to_string())).await;
@@ -52,6 +57,7 @@ pub async fn run(
                         ctx,
                         Arc::new(command.clone()),
                         command.channel_id,
+                        command.user.id,
                         config,
                         20,
                     )
@@ -64,15 +70,30 @@ pub async fn run(
     }
 }
 
+/// Whether the channel could have had a session of this server, that could be brought back.
+/// Channels archived before sessions were recorded have no record, for those reconnecting
+/// falls back to looking for an export in the channel.
+pub async fn had_session(channel_id: ChannelId, guild_id: GuildId) -> bool {
+    match get_channel_info(channel_id).await {
+        Ok(info) => info.is_none_or(|info| info.guild_id == guild_id),
+        Err(err) => {
+            // Better to look for an export than to turn people away.
+            error!(error = %err, "Failed to get channel info");
+            true
+        }
+    }
+}
+
 async fn reconnect(
     ctx: Context,
     i: impl ToInteraction,
     channel_id: ChannelId,
+    user: UserId,
     config: Config,
     limit: usize,
 ) -> Result<(), anyhow::Error> {
     match find_attachment(&ctx, &i, channel_id, limit).await {
-        Some(att) => new_db_from_attachment(ctx.clone(), i, channel_id, config, att).await,
+        Some(att) => new_db_from_attachment(ctx.clone(), i, channel_id, user, config, att).await,
         None => {
             ephemeral_interaction_edit(
                 &ctx,
@@ -90,6 +111,7 @@ pub async fn new_db_from_attachment(
     ctx: Context,
     i: impl ToInteraction,
     channel_id: ChannelId,
+    user: UserId,
     config: Config,
     att: Attachment,
 ) -> Result<(), anyhow::Error> {
@@ -99,24 +121,52 @@ pub async fn new_db_from_attachment(
         .guild()
         .ok_or(anyhow!("Not in a guild"))?;
 
-    match create_db_instance(&config).await {
+    let _room = match make_room(&ctx, channel.guild_id, user, &config).await {
+        Ok(room) => room,
+        Err(err) => return err.edit(&ctx, i).await,
+    };
+    let previous = get_channel_info(channel.id).await.unwrap_or_else(|err| {
+        error!(error = %err, "Failed to get channel info");
+        None
+    });
+
+    match create_db_instance(&config, config.engine, channel.id).await {
         Ok(db) => {
             match register_db(
                 ctx.clone(),
                 db.clone(),
+                config.engine,
                 channel.clone(),
                 config.clone(),
                 crate::ConnType::ConnectedChannel,
                 true,
+                user,
             )
             .await
             {
                 Ok(conn) => {
+                    if let Err(err) = channel_info::record_reconnected(
+                        channel.id,
+                        channel.guild_id,
+                        user,
+                        previous,
+                    )
+                    .await
+                    {
+                        error!(error = %err, "Failed to record reconnected session");
+                    }
                     ephemeral_interaction_edit(&ctx, i.clone(), "Session loading!", "Successfully created a new session, registered it with this channel and now loading your export.", None).await?;
                     if let Err(err) = conn.import_from_attachment(&ctx, i.clone(), &att).await {
                         error!(error = %err, "Error importing from attachment")
                     }
-                    show(&ctx, &channel, conn.conn_type, &config).await
+                    show(&ctx, &channel, conn.conn_type.clone(), conn.engine, &config).await?;
+                    update_field(
+                        &ctx,
+                        &channel.id,
+                        LOCATION_FIELD,
+                        Some(conn.location().await.to_string()),
+                    )
+                    .await
                 }
                 Err(e) => CmdError::RegisterDB(e).edit(&ctx, i).await,
             }
@@ -0,0 +1,95 @@
+use serenity::model::prelude::application_command::ApplicationCommandInteraction;
+use serenity::model::prelude::command::CommandOptionType;
+
+use serenity::builder::CreateApplicationCommand;
+use serenity::prelude::Context;
+use tokio::time::Instant;
+
+use crate::checkpoints;
+use crate::utils::{ephemeral_interaction, ephemeral_interaction_edit, system_message, CmdError};
+use crate::DBCONNS;
+
+pub async fn run(
+    command: &ApplicationCommandInteraction,
+    ctx: Context,
+) -> Result<(), anyhow::Error> {
+    let conn = match DBCONNS.lock().await.get_mut(command.channel_id.as_u64()) {
+        Some(c) => {
+            c.last_used = Instant::now();
+            c.clone()
+        }
+        None => return CmdError::NoSession.reply(&ctx, command).await,
+    };
+
+    let name = match command
+        .data
+        .options
+        .first()
+        .and_then(|o| o.value.as_ref())
+        .and_then(|v| v.as_str())
+    {
+        Some(name) => name.to_string(),
+        None => {
+            return CmdError::ExpectedArgument("a checkpoint name".to_string())
+                .reply(&ctx, command)
+                .await
+        }
+    };
+
+    if !conn.checkpoints.iter().any(|c| c.name == name) {
+        return CmdError::UnknownCheckpoint(name).reply(&ctx, command).await;
+    }
+
+    ephemeral_interaction(
+        &ctx,
+        command,
+        "Rolling back",
+        format!("Restoring checkpoint `{name}`, this may take a while."),
+        None,
+    )
+    .await?;
+
+    let export = match checkpoints::load(command.channel_id, &name).await {
+        Ok(export) => export,
+        Err(err) => return CmdError::RollbackFailed(err).edit(&ctx, command).await,
+    };
+
+    if let Err(err) = conn.restore(&export).await {
+        return CmdError::RollbackFailed(err).edit(&ctx, command).await;
+    }
+
+    ephemeral_interaction_edit(
+        &ctx,
+        command,
+        "Rolled back",
+        format!("This session has been restored to checkpoint `{name}`."),
+        Some(true),
+    )
+    .await?;
+    system_message(
+        &ctx,
+        &command.channel_id,
+        "Session rolled back",
+        format!(
+            "<@{}> restored this session to checkpoint `{name}`.",
+            command.user.id
+        ),
+        Some(true),
+        None,
+        None,
+    )
+    .await
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("rollback")
+        .description("Restore this session's database to a checkpoint")
+        .create_option(|option| {
+            option
+                .name("name")
+                .description("Name of the checkpoint to restore")
+                .kind(CommandOptionType::String)
+                .required(true)
+        })
+}
@@ -0,0 +1,175 @@
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::application_command::{
+    ApplicationCommandInteraction, CommandDataOption,
+};
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::prelude::Context;
+use tokio::time::{Duration, Instant};
+
+use crate::schedules::{self, MAX_SCHEDULES, MIN_INTERVAL};
+use crate::utils::{ephemeral_interaction, user_interaction, CmdError};
+use crate::DBCONNS;
+
+pub async fn run(
+    command: &ApplicationCommandInteraction,
+    ctx: Context,
+) -> Result<(), anyhow::Error> {
+    let conn = match DBCONNS.lock().await.get_mut(command.channel_id.as_u64()) {
+        Some(c) => {
+            c.last_used = Instant::now();
+            c.clone()
+        }
+        None => return CmdError::NoSession.reply(&ctx, command).await,
+    };
+
+    let Some(CommandDataOption { name, options, .. }) = command.data.options.first() else {
+        return CmdError::NoSubCommand.reply(&ctx, command).await;
+    };
+
+    match name.as_str() {
+        "add" => {
+            let mut interval = None;
+            let mut query = None;
+            for option in options {
+                match option.name.as_str() {
+                    "interval" => interval = option.value.as_ref().and_then(|v| v.as_u64()),
+                    "query" => query = option.value.as_ref().and_then(|v| v.as_str()),
+                    _ => {}
+                }
+            }
+            let (Some(interval), Some(query)) = (interval, query) else {
+                return CmdError::ExpectedArgument("an interval and a query".to_string())
+                    .reply(&ctx, command)
+                    .await;
+            };
+            let interval = Duration::from_secs(interval).max(MIN_INTERVAL);
+
+            let Some(id) = schedules::add(
+                ctx.http.clone(),
+                command.channel_id,
+                &conn,
+                interval,
+                query.to_string(),
+                command.user.id,
+            )
+            .await
+            else {
+                return CmdError::TooManySchedules(MAX_SCHEDULES)
+                    .reply(&ctx, command)
+                    .await;
+            };
+
+            user_interaction(
+                &ctx,
+                command,
+                &command.user,
+                "Query scheduled",
+                format!(
+                    "Schedule `{id}` will run every {}:\n```sql\n{query}\n```",
+                    humantime::format_duration(interval)
+                ),
+                Some(true),
+            )
+            .await
+        }
+        "list" => {
+            let schedules = conn.schedules.lock().await;
+            let description = if schedules.is_empty() {
+                "There are no scheduled queries in this session, add one with `/schedule add`."
+                    .to_string()
+            } else {
+                schedules
+                    .values()
+                    .map(|(s, _)| {
+                        format!(
+                            "`{}` every {} by <@{}>\n```sql\n{}\n```",
+                            s.id,
+                            humantime::format_duration(s.interval),
+                            s.creator,
+                            s.query
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+            ephemeral_interaction(&ctx, command, "Scheduled queries", description, None).await
+        }
+        "remove" => {
+            let Some(id) = options
+                .iter()
+                .find(|o| o.name == "id")
+                .and_then(|o| o.value.as_ref())
+                .and_then(|v| v.as_u64())
+            else {
+                return CmdError::ExpectedArgument("a schedule id".to_string())
+                    .reply(&ctx, command)
+                    .await;
+            };
+            let id = id as u32;
+            if !conn.remove_schedule(id).await {
+                return CmdError::UnknownSchedule(id).reply(&ctx, command).await;
+            }
+            user_interaction(
+                &ctx,
+                command,
+                &command.user,
+                "Schedule removed",
+                format!("Schedule `{id}` will no longer run."),
+                Some(true),
+            )
+            .await
+        }
+        _ => {
+            CmdError::InvalidSubCommand(name.to_string())
+                .reply(&ctx, command)
+                .await
+        }
+    }
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("schedule")
+        .description("Run queries in the background of this session")
+        .create_option(|option| {
+            option
+                .name("add")
+                .description("Run a query on an interval")
+                .kind(CommandOptionType::SubCommand)
+                .create_sub_option(|option| {
+                    option
+                        .name("interval")
+                        .description("Number of seconds between runs")
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(MIN_INTERVAL.as_secs())
+                        .required(true)
+                })
+                .create_sub_option(|option| {
+                    option
+                        .name("query")
+                        .description("Query to run")
+                        .kind(CommandOptionType::String)
+                        .required(true)
+                })
+        })
+        .create_option(|option| {
+            option
+                .name("list")
+                .description("List the scheduled queries of this session")
+                .kind(CommandOptionType::SubCommand)
+        })
+        .create_option(|option| {
+            option
+                .name("remove")
+                .description("Stop a scheduled query")
+                .kind(CommandOptionType::SubCommand)
+                .create_sub_option(|option| {
+                    option
+                        .name("id")
+                        .description("Id of the schedule, as shown by /schedule list")
+                        .kind(CommandOptionType::Integer)
+                        .min_int_value(1)
+                        .required(true)
+                })
+        })
+}
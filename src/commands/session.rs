@@ -0,0 +1,44 @@
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::application_command::ApplicationCommandInteraction;
+use serenity::model::prelude::InteractionResponseType::DeferredChannelMessageWithSource;
+use serenity::prelude::Context;
+
+use crate::session_info;
+use crate::utils::CmdError;
+use crate::DBCONNS;
+
+pub async fn run(
+    command: &ApplicationCommandInteraction,
+    ctx: Context,
+) -> Result<(), anyhow::Error> {
+    let Some(conn) = DBCONNS
+        .lock()
+        .await
+        .get(command.channel_id.as_u64())
+        .cloned()
+    else {
+        return CmdError::NoSession.reply(&ctx, command).await;
+    };
+    // Counting records can take a while on large sessions.
+    command
+        .create_interaction_response(&ctx, |r| {
+            r.kind(DeferredChannelMessageWithSource)
+                .interaction_response_data(|d| d.ephemeral(true))
+        })
+        .await?;
+    match session_info::embed(&conn, command.channel_id).await {
+        Ok(embed) => {
+            command
+                .edit_original_interaction_response(&ctx, |m| m.set_embed(embed))
+                .await?;
+            Ok(())
+        }
+        Err(err) => CmdError::SessionInfo(err).edit(&ctx, command).await,
+    }
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("session")
+        .description("Show the tables, definitions and lifetime of the session in this channel")
+}
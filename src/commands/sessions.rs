@@ -0,0 +1,24 @@
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::application_command::ApplicationCommandInteraction;
+use serenity::model::Permissions;
+use serenity::prelude::Context;
+
+use crate::components::session_browser;
+use crate::utils::CmdError;
+
+pub async fn run(
+    command: &ApplicationCommandInteraction,
+    ctx: Context,
+) -> Result<(), anyhow::Error> {
+    match command.guild_id {
+        Some(guild_id) => session_browser::show(&ctx, command, guild_id).await,
+        None => CmdError::NoGuild.reply(&ctx, command).await,
+    }
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("sessions")
+        .description("Browse, export, extend and clean the sessions running in this server")
+        .default_member_permissions(Permissions::MANAGE_CHANNELS)
+}
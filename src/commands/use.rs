@@ -0,0 +1,151 @@
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::application_command::ApplicationCommandInteraction;
+use serenity::model::prelude::autocomplete::AutocompleteInteraction;
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::prelude::Context;
+use tokio::time::Instant;
+
+use crate::components::configurable_session::update_field;
+use crate::location::{self, Location, LOCATION_FIELD};
+use crate::utils::{user_interaction, CmdError};
+use crate::DBCONNS;
+
+/// Discord shows at most this many autocomplete choices.
+const MAX_CHOICES: usize = 25;
+
+pub async fn run(
+    command: &ApplicationCommandInteraction,
+    ctx: Context,
+) -> Result<(), anyhow::Error> {
+    let conn = match DBCONNS.lock().await.get_mut(command.channel_id.as_u64()) {
+        Some(c) => {
+            c.last_used = Instant::now();
+            c.clone()
+        }
+        None => return CmdError::NoSession.reply(&ctx, command).await,
+    };
+
+    let mut namespace = None;
+    let mut database = None;
+    for option in &command.data.options {
+        match option.name.as_str() {
+            "namespace" => namespace = option.value.as_ref().and_then(|v| v.as_str()),
+            "database" => database = option.value.as_ref().and_then(|v| v.as_str()),
+            _ => {}
+        }
+    }
+    let (Some(namespace), Some(database)) = (namespace, database) else {
+        return CmdError::ExpectedArgument("a namespace and a database".to_string())
+            .reply(&ctx, command)
+            .await;
+    };
+    let location = Location {
+        namespace: namespace.to_string(),
+        database: database.to_string(),
+    };
+
+    if let Err(err) = conn.use_location(location.clone()).await {
+        return CmdError::UseFailed(err.into()).reply(&ctx, command).await;
+    }
+    if let Err(err) = update_field(
+        &ctx,
+        &command.channel_id,
+        LOCATION_FIELD,
+        Some(location.to_string()),
+    )
+    .await
+    {
+        warn!(error = %err, "Failed to update session panel");
+    }
+
+    user_interaction(
+        &ctx,
+        command,
+        &command.user,
+        "Switched namespace and database",
+        format!("Queries in this session now run in {location}.\nUsers signed in with `/auth` as a namespace, database or record user stay where they signed in."),
+        Some(true),
+    )
+    .await
+}
+
+/// Suggests the namespaces and databases that exist in the session.
+pub async fn autocomplete(
+    interaction: &AutocompleteInteraction,
+    ctx: Context,
+) -> Result<(), anyhow::Error> {
+    let Some(conn) = DBCONNS
+        .lock()
+        .await
+        .get(interaction.channel_id.as_u64())
+        .cloned()
+    else {
+        return Ok(());
+    };
+    let Some(focused) = interaction.data.options.iter().find(|o| o.focused) else {
+        return Ok(());
+    };
+    let typed = focused
+        .value
+        .as_ref()
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+
+    let names = match focused.name.as_str() {
+        "namespace" => location::namespaces(&conn.root().await).await?,
+        "database" => {
+            let namespace = match interaction
+                .data
+                .options
+                .iter()
+                .find(|o| o.name == "namespace")
+                .and_then(|o| o.value.as_ref())
+                .and_then(|v| v.as_str())
+            {
+                Some(namespace) if !namespace.is_empty() => namespace.to_string(),
+                _ => conn.location().await.namespace,
+            };
+            // The namespace may not exist yet, in which case it has no databases either.
+            location::databases(&conn.root().await, &namespace)
+                .await
+                .unwrap_or_default()
+        }
+        _ => return Ok(()),
+    };
+
+    interaction
+        .create_autocomplete_response(&ctx, |r| {
+            for name in names
+                .iter()
+                .filter(|name| name.starts_with(typed))
+                .take(MAX_CHOICES)
+            {
+                r.add_string_choice(name, name);
+            }
+            r
+        })
+        .await?;
+    Ok(())
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("use")
+        .description("Switch the namespace and database queries in this session run in")
+        .create_option(|option| {
+            option
+                .name("namespace")
+                .description("Namespace to use, created if it doesn't exist")
+                .kind(CommandOptionType::String)
+                .set_autocomplete(true)
+                .required(true)
+        })
+        .create_option(|option| {
+            option
+                .name("database")
+                .description("Database to use, created if it doesn't exist")
+                .kind(CommandOptionType::String)
+                .set_autocomplete(true)
+                .required(true)
+        })
+}
@@ -1,5 +1,7 @@
+use std::collections::HashMap;
+
 use crate::{
-    config::Config,
+    config::{CapabilityPolicy, Config, Engine},
     utils::{ephemeral_interaction, CmdError, BOT_VERSION},
     DB,
 };
@@ -33,8 +35,20 @@ pub async fn show(ctx: &Context, channel: &ChannelId, config: &Config) -> Result
             .field("Archived Channel group is", config.archive_channel.mention(), true)
             .field("Session lifetime after last query is ", format_duration(config.ttl), true)
             .field("Query timeout is set to ", format_duration(config.timeout), true)
+            .field("Sessions are warned before expiring ", match config.ttl_warning.is_zero() {
+                true => "never".to_string(),
+                false => format!("{} before", format_duration(config.ttl_warning)),
+            }, true)
+            .field("Longest a session can live is ", match config.max_lifetime.is_zero() {
+                true => "unlimited".to_string(),
+                false => format_duration(config.max_lifetime).to_string(),
+            }, true)
             .field("Output format is ", if config.json { "JSON" } else { "SQL-like" }, true)
             .field("Output is ", if config.pretty { "prettified" } else { "raw" }, true)
+            .field("Default storage engine is ", config.engine.label(), true)
+            .field("Capabilities of new sessions", config.capabilities.describe(), false)
+            .field("Quotas", config.quotas.describe(), false)
+            .field("Session limits", config.session_limits.describe(), false)
         })
         .components(|c| {
             c.create_action_row(|r| {
@@ -51,10 +65,29 @@ pub async fn show(ctx: &Context, channel: &ChannelId, config: &Config) -> Result
                          .create_option(|o| o.default_selection(!config.pretty).label("Raw output").value("false"))
                     })
                 })
+            }).create_action_row(|r|{
+                r.create_select_menu(|s| {
+                    s.custom_id("configurable_server:engine").placeholder("Default storage engine").min_values(1).max_values(1).options(|o| {
+                        for engine in Engine::ALL {
+                            o.create_option(|o| o.default_selection(config.engine == engine).label(engine.label()).value(engine.option_value()));
+                        }
+                        o
+                    })
+                })
+            }).create_action_row(|r|{
+                r.create_select_menu(|s| {
+                    s.custom_id("configurable_server:capabilities").placeholder("Allowed capabilities").min_values(0).max_values(2).options(|o| {
+                        o.create_option(|o| o.default_selection(config.capabilities.scripting).label("Scripting").value("scripting"))
+                         .create_option(|o| o.default_selection(config.capabilities.guest_access).label("Guest access").value("guest_access"))
+                    })
+                })
             }).create_action_row(|r| {
                 r
                 .create_button(|b| b.custom_id("configurable_server:ttl").label("Change TTL").style(Success).emoji('⏳'))
                 .create_button(|b| b.custom_id("configurable_server:timeout").label("Change Query timeout").style(Success).emoji('⌛'))
+                .create_button(|b| b.custom_id("configurable_server:ttl_warning").label("Change expiry warning").style(Success).emoji('⏰'))
+                .create_button(|b| b.custom_id("configurable_server:max_lifetime").label("Change max lifetime").style(Success).emoji('⌚'))
+                .create_button(|b| b.custom_id("configurable_server:capability_targets").label("Change functions and network").style(Success).emoji('🛡'))
             })
         })
     }).await?;
@@ -104,7 +137,10 @@ pub async fn handle_component(
             error!("Error while getting config: {}", err);
             return CmdError::GetConfig(err).reply(ctx, event).await;
         }
-        ("ttl", Ok(Some(config))) | ("timeout", Ok(Some(config))) => {
+        ("ttl", Ok(Some(config)))
+        | ("timeout", Ok(Some(config)))
+        | ("ttl_warning", Ok(Some(config)))
+        | ("max_lifetime", Ok(Some(config))) => {
             event
                 .create_interaction_response(&ctx, |a| {
                     a.kind(Modal).interaction_response_data(|d| {
@@ -117,6 +153,8 @@ pub async fn handle_component(
                                         .value(match id {
                                             "ttl" => format_duration(config.ttl),
                                             "timeout" => format_duration(config.timeout),
+                                            "ttl_warning" => format_duration(config.ttl_warning),
+                                            "max_lifetime" => format_duration(config.max_lifetime),
                                             _ => unreachable!(),
                                         })
                                         .placeholder("Duration (5m30s)")
@@ -132,12 +170,69 @@ pub async fn handle_component(
                 })
                 .await?;
         }
-        ("format", Ok(Some(mut config))) | ("prettify", Ok(Some(mut config))) => {
-            match id {
-                "format" => config.json = values[0] == "json",
-                "prettify" => config.pretty = values[0] == "true",
+        ("capability_targets", Ok(Some(config))) => {
+            let policy = &config.capabilities;
+            event
+                .create_interaction_response(&ctx, |a| {
+                    a.kind(Modal).interaction_response_data(|d| {
+                        d.components(|c| {
+                            for (target, label, targets) in [
+                                (
+                                    "allow_functions",
+                                    "Allowed functions",
+                                    &policy.allow_functions,
+                                ),
+                                ("deny_functions", "Denied functions", &policy.deny_functions),
+                                ("allow_net", "Allowed network targets", &policy.allow_net),
+                                ("deny_net", "Denied network targets", &policy.deny_net),
+                            ] {
+                                c.create_action_row(|r| {
+                                    r.create_input_text(|i| {
+                                        i.custom_id(target)
+                                            .label(label)
+                                            .style(Short)
+                                            .value(CapabilityPolicy::format_targets(targets))
+                                            .placeholder("Comma separated, * for all or none")
+                                            .max_length(1000)
+                                            .required(false)
+                                    })
+                                });
+                            }
+                            c
+                        })
+                        .custom_id(format!("configurable_server:{id}"))
+                        .title("Setting new capability targets")
+                    })
+                })
+                .await?;
+        }
+        ("format", Ok(Some(mut config)))
+        | ("prettify", Ok(Some(mut config)))
+        | ("engine", Ok(Some(mut config)))
+        | ("capabilities", Ok(Some(mut config))) => {
+            let description = match id {
+                "format" => {
+                    config.json = values[0] == "json";
+                    format!("{} is now set to {}", id, values[0])
+                }
+                "prettify" => {
+                    config.pretty = values[0] == "true";
+                    format!("{} is now set to {}", id, values[0])
+                }
+                "engine" => {
+                    config.engine = Engine::from_option(&values[0]).unwrap_or(config.engine);
+                    format!("{} is now set to {}", id, values[0])
+                }
+                "capabilities" => {
+                    config.capabilities.scripting = values.iter().any(|v| v == "scripting");
+                    config.capabilities.guest_access = values.iter().any(|v| v == "guest_access");
+                    format!(
+                        "New sessions will have these capabilities:\n{}",
+                        config.capabilities.describe()
+                    )
+                }
                 _ => unreachable!(),
-            }
+            };
             let updated: Result<Option<Config>, surrealdb::Error> = DB
                 .update(("guild_config", guild.to_string()))
                 .content(config)
@@ -145,14 +240,8 @@ pub async fn handle_component(
             match updated {
                 Ok(Some(_)) => {
                     dirty = true;
-                    ephemeral_interaction(
-                        ctx,
-                        event,
-                        "Config updated!",
-                        format!("{} is now set to {}", id, values[0]),
-                        Some(true),
-                    )
-                    .await?;
+                    ephemeral_interaction(ctx, event, "Config updated!", description, Some(true))
+                        .await?;
                 }
                 Ok(None) => {
                     unreachable!(
@@ -195,7 +284,7 @@ pub async fn handle_modal(
 ) -> Result<()> {
     match id {
         // Use humantime.parse_duration to parse component values.
-        "ttl" | "timeout" => {
+        "ttl" | "timeout" | "ttl_warning" | "max_lifetime" => {
             if let ActionRowComponent::InputText(InputText { value, .. }) = &values[0].components[0]
             {
                 let duration = match humantime::parse_duration(value) {
@@ -220,6 +309,8 @@ pub async fn handle_modal(
                         match id {
                             "ttl" => config.ttl = duration,
                             "timeout" => config.timeout = duration,
+                            "ttl_warning" => config.ttl_warning = duration,
+                            "max_lifetime" => config.max_lifetime = duration,
                             _ => unreachable!(),
                         }
                         let updated: Result<Option<Config>, surrealdb::Error> = DB
@@ -256,6 +347,66 @@ pub async fn handle_modal(
                 }
             }
         }
+        "capability_targets" => {
+            let mut targets = HashMap::new();
+            for row in values {
+                if let Some(ActionRowComponent::InputText(InputText {
+                    custom_id, value, ..
+                })) = row.components.first()
+                {
+                    targets.insert(custom_id.as_str(), CapabilityPolicy::parse_targets(value));
+                }
+            }
+            let result: Result<Option<Config>, surrealdb::Error> =
+                DB.select(("guild_config", guild.to_string())).await;
+            match result {
+                Ok(Some(mut config)) => {
+                    let policy = &mut config.capabilities;
+                    for (target, list) in [
+                        ("allow_functions", &mut policy.allow_functions),
+                        ("deny_functions", &mut policy.deny_functions),
+                        ("allow_net", &mut policy.allow_net),
+                        ("deny_net", &mut policy.deny_net),
+                    ] {
+                        if let Some(value) = targets.remove(target) {
+                            *list = value;
+                        }
+                    }
+                    if let Err(err) = policy.build() {
+                        return CmdError::InvalidCapabilities(err).reply(ctx, event).await;
+                    }
+                    let description = policy.describe();
+                    let updated: Result<Option<Config>, surrealdb::Error> = DB
+                        .update(("guild_config", guild.to_string()))
+                        .content(config)
+                        .await;
+                    match updated {
+                        Ok(Some(_)) => {
+                            ephemeral_interaction(
+                                ctx,
+                                event,
+                                "Config updated",
+                                format!(
+                                    "New sessions will have these capabilities:\n{description}"
+                                ),
+                                Some(true),
+                            )
+                            .await?;
+                        }
+                        Ok(None) => {
+                            unreachable!(
+                                "Update returned None even though it should have always returned Some"
+                            )
+                        }
+                        Err(err) => {
+                            error!("Error while updating config: {}", err);
+                            CmdError::UpdateConfig(err).reply(ctx, event).await?;
+                        }
+                    }
+                }
+                _ => unreachable!("Should've returned long before..."),
+            }
+        }
         _ => unreachable!(),
     }
 
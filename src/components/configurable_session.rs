@@ -1,10 +1,14 @@
 use std::{borrow::Cow, sync::Arc};
 
 use crate::{
-    config::Config,
+    config::{Config, Engine},
+    db_utils::get_channel_info,
+    identity::{self, IDENTITY_FIELD},
+    location::{Location, LOCATION_FIELD},
+    session_info,
     utils::{
-        clean_channel, ephemeral_interaction, user_interaction, CmdError, BOT_VERSION,
-        SURREALDB_VERSION,
+        clean_channel, ephemeral_interaction, ephemeral_interaction_edit, system_message,
+        unix_timestamp, user_interaction, CmdError, BOT_VERSION, SURREALDB_VERSION,
     },
     ConnType, BIG_QUERY_SENT_KEY, BIG_QUERY_VARS_KEY, DB, DBCONNS,
 };
@@ -12,6 +16,7 @@ use crate::{
 use anyhow::Result;
 use humantime::format_duration;
 use serenity::{
+    builder::CreateEmbed,
     model::prelude::{
         component::{
             ActionRow, ActionRowComponent,
@@ -22,26 +27,31 @@ use serenity::{
         message_component::MessageComponentInteraction,
         modal::ModalSubmitInteraction,
         ChannelId, GuildChannel,
-        InteractionResponseType::Modal,
-        ReactionType,
+        InteractionResponseType::{DeferredChannelMessageWithSource, Modal},
+        Message, MessageId, ReactionType,
     },
     prelude::Context,
 };
 use tracing::Instrument;
 
+pub const PANEL_TITLE: &str = "Your SurrealDB session";
+pub const CHECKPOINTS_FIELD: &str = "Checkpoints";
+pub const PARAMS_FIELD: &str = "Parameters";
+
 /// Send a message to the server with prebuilt components for DB channel configuration management
 #[instrument(skip(ctx, channel, conn, config))]
 pub async fn show(
     ctx: &Context,
     channel: &GuildChannel,
     conn: ConnType,
+    engine: Engine,
     config: &Config,
 ) -> Result<()> {
     let msg = channel.send_message(&ctx, |message| {
         message
         .embed(|embed| {
             embed
-            .title("Your SurrealDB session")
+            .title(PANEL_TITLE)
             .description(format!("{} \n* You can use `/load` to load a premade dataset or your own SurrealQL from a file.\n* You are using SurrealDB {}.", match conn {
                 ConnType::ConnectedChannel => "This channel is now connected to a SurrealDB instance. \nTry writing some SurrealQL! \n",
                 ConnType::EphemeralChannel => "This brand new channel is now connected to a SurrealDB instance. \nTry writing some SurrealQL! \n\n* You can use `/share` to add friends to this channel.",
@@ -52,6 +62,10 @@ pub async fn show(
             })
             .field("Session lifetime after last query is ", format_duration(config.ttl), true)
             .field("Query timeout is set to ", format_duration(config.timeout), true)
+            .field("Storage engine is ", engine.label(), true)
+            .field(LOCATION_FIELD, Location::default().to_string(), false)
+            .field(IDENTITY_FIELD, identity::describe(&Default::default()), false)
+            .field("Query parameters", "`$discord_user` your user id, `$discord_user_name` your username, `$channel_id` this channel's id and `$message_id` the id of the message with your query", false)
         })
         .components(|c| {
             c.create_action_row(|r| {
@@ -78,10 +92,16 @@ pub async fn show(
             }).create_action_row(|mut r| {
                 r = r.create_button(|b| b.custom_id("configurable_session:big_query").label("Big Query").style(Primary).emoji('📝'));
                 r = r.create_button(|b| b.custom_id("configurable_session:export").label("Export").style(Success).emoji('📃'));
+                if engine == Engine::SurrealKvVersioned {
+                    r = r.create_button(|b| b.custom_id("configurable_session:versions").label("Time travel").style(Secondary).emoji('⏪'));
+                }
                 if matches!(conn, ConnType::Thread) {
                     r = r.create_button(|b| b.custom_id("configurable_session:rename_thread").label("Rename thread").style(Secondary).emoji(TryInto::<ReactionType>::try_into("✏️").expect("Failed to convert emoji")));
                 }
                 r.create_button(|b| b.custom_id("configurable_session:stop").label("Stop session").style(Danger).emoji('💣'))
+            }).create_action_row(|r| {
+                r.create_button(|b| b.custom_id("configurable_session:fork").label("Fork").style(Secondary).emoji('🍴'))
+                 .create_button(|b| b.custom_id("configurable_session:info").label("Session info").style(Secondary).emoji('📊'))
             })
         })
     }).await?;
@@ -96,11 +116,9 @@ pub async fn show(
                 match channel_clone.pins(&_ctx).await {
                     Ok(pins) => {
                         let old_pin = pins.iter().find(|m| {
-                            m.embeds.iter().any(|e| {
-                                e.title
-                                    .as_ref()
-                                    .map_or(false, |t| t == "Your SurrealDB session")
-                            })
+                            m.embeds
+                                .iter()
+                                .any(|e| e.title.as_ref().map_or(false, |t| t == PANEL_TITLE))
                         });
 
                         if let Some(old_pin) = old_pin {
@@ -120,6 +138,55 @@ pub async fn show(
     Ok(())
 }
 
+/// Finds the session panel sent by `show`, looking at pins first and then at recent messages.
+async fn find_panel(ctx: &Context, channel: &ChannelId) -> Result<Option<Message>> {
+    let is_panel = |m: &Message| {
+        m.author.bot
+            && m.embeds
+                .iter()
+                .any(|e| e.title.as_deref() == Some(PANEL_TITLE))
+    };
+    if let Some(panel) = channel.pins(ctx).await?.into_iter().find(is_panel) {
+        return Ok(Some(panel));
+    }
+    Ok(channel
+        .messages(ctx, |r| r.limit(50))
+        .await?
+        .into_iter()
+        .find(is_panel))
+}
+
+/// Sets, replaces or removes (when `value` is `None`) a field on the session panel of a channel.
+#[instrument(skip(ctx, value))]
+pub async fn update_field(
+    ctx: &Context,
+    channel: &ChannelId,
+    name: &str,
+    value: Option<String>,
+) -> Result<()> {
+    let Some(mut panel) = find_panel(ctx, channel).await? else {
+        debug!("No session panel found to update");
+        return Ok(());
+    };
+    let Some(embed) = panel.embeds.first().cloned() else {
+        return Ok(());
+    };
+    let mut fields: Vec<_> = embed
+        .fields
+        .iter()
+        .filter(|f| f.name != name)
+        .map(|f| (f.name.clone(), f.value.clone(), f.inline))
+        .collect();
+    if let Some(value) = value {
+        fields.push((name.to_string(), value, false));
+    }
+    let mut updated = CreateEmbed::from(embed);
+    updated.0.remove("fields");
+    updated.fields(fields);
+    panel.edit(ctx, |m| m.set_embed(updated)).await?;
+    Ok(())
+}
+
 #[instrument(skip(ctx, event))]
 pub async fn handle_component(
     ctx: &Context,
@@ -155,6 +222,29 @@ pub async fn handle_component(
             )
             .await?;
         }
+        ("extend", true) => {
+            let (expires_at, at_max_lifetime) = {
+                let mut conns = DBCONNS.lock().await;
+                let conn = conns
+                    .get_mut(&channel.0)
+                    .expect("DB disappeared between now above check");
+                conn.extend();
+                (conn.expires_at(), conn.at_max_lifetime())
+            };
+            let expires = unix_timestamp(expires_at);
+            user_interaction(
+                ctx,
+                event,
+                &event.user,
+                "Session extended",
+                match at_max_lifetime {
+                    true => format!("This session now expires <t:{expires}:R>, the longest this server lets sessions live."),
+                    false => format!("This session now expires <t:{expires}:R> unless it's used again."),
+                },
+                Some(true),
+            )
+            .await?;
+        }
         ("export", true) => {
             debug!("Exporting database");
             let conn = DBCONNS
@@ -181,7 +271,193 @@ pub async fn handle_component(
                 }
             }
         }
+        ("versions", true) => {
+            let conn = DBCONNS
+                .lock()
+                .await
+                .get(&channel.0)
+                .expect("DB disappeared between now above check")
+                .clone();
+            if conn.engine != Engine::SurrealKvVersioned {
+                ephemeral_interaction(
+                    ctx,
+                    event,
+                    "Time travel unavailable",
+                    "This session is not versioned, create a new session with the `Versioned SurrealKV` engine to use `VERSION` queries.",
+                    Some(false),
+                )
+                .await?;
+                return Ok(());
+            }
+            let versions = conn.versions.lock().await.clone();
+            let description = match versions.back() {
+                Some(latest) => format!(
+                    "You can query the database as it was at any of these points in time:\n{}\n\nFor example:\n```sql\nSELECT * FROM person VERSION {latest};\n```",
+                    versions
+                        .iter()
+                        .rev()
+                        .map(|v| format!("* `{v}`"))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                ),
+                None => "No queries have been run in this session yet, try writing some SurrealQL first!\nOnce you have, you can add `VERSION d'<timestamp>'` to a `SELECT` to see the data as it was at that point in time.".to_string(),
+            };
+            ephemeral_interaction(ctx, event, "Time travel", description, None).await?;
+        }
+        (id, true) if id.starts_with("undo:") => {
+            let message_id = MessageId(id.trim_start_matches("undo:").parse()?);
+            let conn = DBCONNS
+                .lock()
+                .await
+                .get(&channel.0)
+                .expect("DB disappeared between now above check")
+                .clone();
+            let export = match conn.take_undo(message_id, event.user.id).await {
+                Ok(export) => export,
+                Err(err) => return err.reply(ctx, event).await,
+            };
+            ephemeral_interaction(
+                ctx,
+                event,
+                "Undoing",
+                "Restoring the session to how it was before this query.",
+                None,
+            )
+            .await?;
+            if let Err(err) = conn.restore(&export).await {
+                return CmdError::UndoFailed(err).edit(ctx, event).await;
+            }
+            event
+                .message
+                .clone()
+                .edit(ctx, |m| m.components(|c| c))
+                .await?;
+            ephemeral_interaction_edit(
+                ctx,
+                event,
+                "Query undone",
+                "The session is back to how it was before this query.",
+                Some(true),
+            )
+            .await?;
+            system_message(
+                ctx,
+                channel,
+                "Query undone",
+                format!(
+                    "<@{}> undid [this query]({}).",
+                    event.user.id,
+                    message_id.link(*channel, event.guild_id)
+                ),
+                Some(true),
+                None,
+                None,
+            )
+            .await?;
+        }
+        (id, true) if id.starts_with("kill_live:") => {
+            let live_id = MessageId(id.trim_start_matches("kill_live:").parse()?);
+            let conn = DBCONNS
+                .lock()
+                .await
+                .get(&channel.0)
+                .expect("DB disappeared between now above check")
+                .clone();
+            if conn.kill_live_query(live_id).await {
+                user_interaction(
+                    ctx,
+                    event,
+                    &event.user,
+                    "Live query killed",
+                    "No more changes will be posted for this live query.",
+                    Some(true),
+                )
+                .await?;
+            } else {
+                ephemeral_interaction(
+                    ctx,
+                    event,
+                    "Live query not running",
+                    "This live query has already been killed or has ended.",
+                    None,
+                )
+                .await?;
+            }
+        }
+        (id, true) if id.starts_with("remove_dashboard:") => {
+            let dashboard_id = MessageId(id.trim_start_matches("remove_dashboard:").parse()?);
+            let conn = DBCONNS
+                .lock()
+                .await
+                .get(&channel.0)
+                .expect("DB disappeared between now above check")
+                .clone();
+            conn.remove_dashboard(dashboard_id).await;
+            user_interaction(
+                ctx,
+                event,
+                &event.user,
+                "Dashboard removed",
+                "The dashboard has been removed.",
+                Some(true),
+            )
+            .await?;
+            channel.delete_message(ctx, dashboard_id).await?;
+        }
+        ("info", true) => {
+            let conn = DBCONNS
+                .lock()
+                .await
+                .get(&channel.0)
+                .expect("DB disappeared between now above check")
+                .clone();
+            event
+                .create_interaction_response(ctx, |r| {
+                    r.kind(DeferredChannelMessageWithSource)
+                        .interaction_response_data(|d| d.ephemeral(true))
+                })
+                .await?;
+            match session_info::embed(&conn, *channel).await {
+                Ok(embed) => {
+                    event
+                        .edit_original_interaction_response(ctx, |m| m.set_embed(embed))
+                        .await?;
+                }
+                Err(err) => CmdError::SessionInfo(err).edit(ctx, event).await?,
+            }
+        }
+        ("fork", true) => {
+            crate::commands::fork::fork(
+                ctx,
+                event,
+                event
+                    .guild_id
+                    .expect("our components are only available in guilds"),
+                event.application_id,
+                &event.user,
+                channel
+                    .to_channel(&ctx)
+                    .await?
+                    .guild()
+                    .expect("our components are only available in guilds"),
+            )
+            .await?;
+        }
         ("stop", true) => {
+            match get_channel_info(*channel).await {
+                Ok(Some(info))
+                    if !info.can_stop(
+                        event.user.id,
+                        event.member.as_ref().and_then(|m| m.permissions),
+                    ) =>
+                {
+                    return CmdError::NotSessionOwner(info.creator)
+                        .reply(ctx, event)
+                        .await;
+                }
+                Ok(_) => {}
+                Err(err) => error!(error = %err, "Failed to get channel info"),
+            }
             debug!("Stopping session per user request");
             clean_channel(
                 channel
@@ -225,44 +501,15 @@ pub async fn handle_component(
                 (Cow::Owned(query), Cow::Owned(vars))
             };
 
-            event
-                .create_interaction_response(&ctx, |a| {
-                    a.kind(Modal).interaction_response_data(|d| {
-                        d.components(|c| {
-                            c.create_action_row(|r| {
-                                r.create_input_text(|i| {
-                                    i.custom_id("configurable_session:big_query")
-                                        .label("Big query")
-                                        .style(Paragraph)
-                                        .placeholder("Your Surreal query")
-                                        .required(true)
-                                        .value(query)
-                                })
-                            })
-                            .create_action_row(|r| {
-                                r.create_input_text(|i| {
-                                    i.custom_id("configurable_session:big_query_variables")
-                                        .label("Variables (as JSON)")
-                                        .style(Paragraph)
-                                        .placeholder("Your Surreal variables (as JSON)")
-                                        .required(false)
-                                        .value(vars)
-                                })
-                            })
-                        })
-                        .custom_id("configurable_session:big_query")
-                        .title("Big Query editor")
-                    })
-                })
-                .await?;
+            big_query_modal(ctx, event, &query, &vars).await?;
         }
         ("reconnect", false) => {
-            let result: Result<Option<Config>, surrealdb::Error> = DB
-                .select((
-                    "guild_config",
-                    &event.guild_id.unwrap_or_default().to_string(),
-                ))
-                .await;
+            let guild_id = event.guild_id.unwrap_or_default();
+            if !crate::commands::reconnect::had_session(*channel, guild_id).await {
+                return CmdError::NoPastSession.reply(ctx, event).await;
+            }
+            let result: Result<Option<Config>, surrealdb::Error> =
+                DB.select(("guild_config", &guild_id.to_string())).await;
             match (event.message.attachments.first(), result) {
                 (Some(att), Ok(Some(config))) => {
                     ephemeral_interaction(
@@ -278,6 +525,7 @@ pub async fn handle_component(
                             ctx.clone(),
                             Arc::new(event.clone()),
                             channel.clone(),
+                            event.user.id,
                             config,
                             att.clone(),
                         )
@@ -339,6 +587,46 @@ pub async fn handle_component(
     Ok(())
 }
 
+/// Opens the Big Query editor, filled with `query` and `vars`.
+pub async fn big_query_modal(
+    ctx: &Context,
+    event: &MessageComponentInteraction,
+    query: &str,
+    vars: &str,
+) -> Result<()> {
+    event
+        .create_interaction_response(ctx, |a| {
+            a.kind(Modal).interaction_response_data(|d| {
+                d.components(|c| {
+                    c.create_action_row(|r| {
+                        r.create_input_text(|i| {
+                            i.custom_id("configurable_session:big_query")
+                                .label("Big query")
+                                .style(Paragraph)
+                                .placeholder("Your Surreal query")
+                                .required(true)
+                                .value(query)
+                        })
+                    })
+                    .create_action_row(|r| {
+                        r.create_input_text(|i| {
+                            i.custom_id("configurable_session:big_query_variables")
+                                .label("Variables (as JSON)")
+                                .style(Paragraph)
+                                .placeholder("Your Surreal variables (as JSON)")
+                                .required(false)
+                                .value(vars)
+                        })
+                    })
+                })
+                .custom_id("configurable_session:big_query")
+                .title("Big Query editor")
+            })
+        })
+        .await?;
+    Ok(())
+}
+
 #[instrument(skip(ctx, event))]
 pub async fn handle_modal(
     ctx: &Context,
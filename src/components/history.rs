@@ -0,0 +1,263 @@
+use anyhow::Result;
+use serenity::{
+    builder::{CreateComponents, CreateEmbed},
+    model::prelude::{
+        application_command::ApplicationCommandInteraction,
+        component::ButtonStyle::{Primary, Secondary},
+        message_component::MessageComponentInteraction,
+        ChannelId,
+        InteractionResponseType::{ChannelMessageWithSource, UpdateMessage},
+    },
+    prelude::Context,
+};
+use tokio::time::Instant;
+
+use crate::{
+    components::configurable_session::big_query_modal,
+    history::{self, HistoryEntry},
+    utils::{ephemeral_interaction, CmdError},
+    DBCONNS,
+};
+
+pub const PAGE_SIZE: usize = 5;
+
+/// Sends the query history of `channel_id`, only visible to the person asking.
+pub async fn show(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    channel_id: ChannelId,
+) -> Result<()> {
+    let (embed, components) = match render(channel_id, 0).await {
+        Ok(page) => page,
+        Err(err) => return CmdError::History(err).reply(ctx, command).await,
+    };
+    command
+        .create_interaction_response(ctx, |r| {
+            r.kind(ChannelMessageWithSource)
+                .interaction_response_data(|d| {
+                    d.set_embed(embed)
+                        .set_components(components)
+                        .ephemeral(true)
+                })
+        })
+        .await?;
+    Ok(())
+}
+
+/// Cuts `text` to at most `max` characters, marking it when it was cut.
+fn shorten(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max.saturating_sub(3)) {
+        Some((end, _)) if text.chars().count() > max => format!("{}...", &text[..end]),
+        _ => text.to_string(),
+    }
+}
+
+/// One page of the history, with a menu to pick one of the queries it lists.
+async fn render(
+    channel_id: ChannelId,
+    page: usize,
+) -> Result<(CreateEmbed, CreateComponents), surrealdb::Error> {
+    let (entries, total) = history::page(channel_id, page, PAGE_SIZE).await?;
+    let pages = (total as usize).div_ceil(PAGE_SIZE).max(1);
+
+    let lines: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "{}\n```sql\n{}\n```",
+                entry.describe(),
+                shorten(&entry.query, 300).replace("```", "`\u{200b}``")
+            )
+        })
+        .collect();
+
+    let mut embed = CreateEmbed::default();
+    embed
+        .title(format!("Query history ({total})"))
+        .description(match lines.is_empty() {
+            true => "No queries were run in this channel yet.".to_string(),
+            false => lines.join("\n"),
+        })
+        .footer(|f| f.text(format!("Page {} of {pages}", page + 1)));
+
+    let mut components = CreateComponents::default();
+    if !entries.is_empty() {
+        components.create_action_row(|r| {
+            r.create_select_menu(|s| {
+                s.custom_id(format!("history:entry:{page}"))
+                    .placeholder("Pick a query to run again...")
+                    .options(|o| {
+                        for entry in &entries {
+                            let Some(key) = &entry.key else {
+                                continue;
+                            };
+                            o.create_option(|o| {
+                                o.label(shorten(entry.query.trim(), 100))
+                                    .description(match entry.error {
+                                        Some(_) => format!("Failed after {}", entry.took()),
+                                        None => format!("Took {}", entry.took()),
+                                    })
+                                    .value(key)
+                            });
+                        }
+                        o
+                    })
+            })
+        });
+    }
+    components.create_action_row(|r| {
+        r.create_button(|b| {
+            b.custom_id(format!("history:page:{}", page.saturating_sub(1)))
+                .label("Previous")
+                .style(Secondary)
+                .disabled(page == 0)
+        })
+        .create_button(|b| {
+            b.custom_id(format!("history:refresh:{page}"))
+                .label("Refresh")
+                .style(Primary)
+                .emoji('🔄')
+        })
+        .create_button(|b| {
+            b.custom_id(format!("history:page:{}", page + 1))
+                .label("Next")
+                .style(Secondary)
+                .disabled(page + 1 >= pages)
+        })
+    });
+    Ok((embed, components))
+}
+
+/// A single query of the history, with buttons to run it again.
+fn render_entry(key: &str, entry: &HistoryEntry, page: usize) -> (CreateEmbed, CreateComponents) {
+    let mut embed = CreateEmbed::default();
+    embed.title("Past query").description(format!(
+        "{}\n```sql\n{}\n```",
+        entry.describe(),
+        shorten(&entry.query, 3800).replace("```", "`\u{200b}``")
+    ));
+    if let Some(vars) = &entry.vars {
+        embed.field(
+            "Variables",
+            format!(
+                "```json\n{}\n```",
+                shorten(
+                    &serde_json::to_string_pretty(vars).unwrap_or_default(),
+                    1000
+                )
+            ),
+            false,
+        );
+    }
+    if let Some(error) = &entry.error {
+        embed.field(
+            "Error",
+            format!("```rust\n{}\n```", shorten(error, 1000)),
+            false,
+        );
+    }
+
+    let mut components = CreateComponents::default();
+    components.create_action_row(|r| {
+        r.create_button(|b| {
+            b.custom_id(format!("history:rerun:{key}"))
+                .label("Re-run")
+                .style(Primary)
+                .emoji('🔁')
+        })
+        .create_button(|b| {
+            b.custom_id(format!("history:big_query:{key}"))
+                .label("Open in Big Query")
+                .style(Primary)
+                .emoji('📝')
+        })
+        .create_button(|b| {
+            b.custom_id(format!("history:page:{page}"))
+                .label("Back")
+                .style(Secondary)
+        })
+    });
+    (embed, components)
+}
+
+pub async fn handle_component(
+    ctx: &Context,
+    event: &MessageComponentInteraction,
+    channel: &ChannelId,
+    id: &str,
+    values: &[String],
+) -> Result<()> {
+    let Some((action, arg)) = id.split_once(':') else {
+        return Ok(());
+    };
+    match action {
+        "page" | "refresh" => {
+            let (embed, components) = match render(*channel, arg.parse()?).await {
+                Ok(page) => page,
+                Err(err) => return CmdError::History(err).reply(ctx, event).await,
+            };
+            update(ctx, event, embed, components).await
+        }
+        "entry" => {
+            let Some(key) = values.first() else {
+                return Ok(());
+            };
+            match history::get(*channel, key).await {
+                Ok(Some(entry)) => {
+                    let (embed, components) = render_entry(key, &entry, arg.parse()?);
+                    update(ctx, event, embed, components).await
+                }
+                Ok(None) => CmdError::NoHistoryEntry.reply(ctx, event).await,
+                Err(err) => CmdError::History(err).reply(ctx, event).await,
+            }
+        }
+        "rerun" | "big_query" => {
+            let entry = match history::get(*channel, arg).await {
+                Ok(Some(entry)) => entry,
+                Ok(None) => return CmdError::NoHistoryEntry.reply(ctx, event).await,
+                Err(err) => return CmdError::History(err).reply(ctx, event).await,
+            };
+            let conn = match DBCONNS.lock().await.get_mut(channel.as_u64()) {
+                Some(c) => {
+                    c.last_used = Instant::now();
+                    c.clone()
+                }
+                None => return CmdError::NoSession.reply(ctx, event).await,
+            };
+            if action == "big_query" {
+                let vars = match &entry.vars {
+                    Some(vars) => serde_json::to_string_pretty(vars).unwrap_or_default(),
+                    None => String::new(),
+                };
+                return big_query_modal(ctx, event, &entry.query, &vars).await;
+            }
+            ephemeral_interaction(
+                ctx,
+                event,
+                "Query processing...",
+                "Your query has been sent to the database and is processing now...",
+                Some(true),
+            )
+            .await?;
+            // Runs as whoever asked, not as whoever first ran it.
+            conn.query(ctx, channel, None, &event.user, entry.query, entry.vars)
+                .await
+        }
+        _ => Ok(()),
+    }
+}
+
+async fn update(
+    ctx: &Context,
+    event: &MessageComponentInteraction,
+    embed: CreateEmbed,
+    components: CreateComponents,
+) -> Result<()> {
+    event
+        .create_interaction_response(ctx, |r| {
+            r.kind(UpdateMessage)
+                .interaction_response_data(|d| d.set_embed(embed).set_components(components))
+        })
+        .await?;
+    Ok(())
+}
@@ -1,2 +1,4 @@
 pub mod configurable_server;
 pub mod configurable_session;
+pub mod history;
+pub mod session_browser;
@@ -0,0 +1,277 @@
+use std::borrow::Cow;
+
+use anyhow::Result;
+use serenity::{
+    builder::{CreateComponents, CreateEmbed},
+    model::prelude::{
+        application_command::ApplicationCommandInteraction,
+        component::ButtonStyle::{Primary, Secondary},
+        message_component::MessageComponentInteraction,
+        AttachmentType, ChannelId, GuildId,
+        InteractionResponseType::{DeferredChannelMessageWithSource, DeferredUpdateMessage},
+    },
+    prelude::Context,
+};
+
+use crate::{
+    channel_info::{self, ChannelInfo},
+    memory,
+    quotas::format_size,
+    utils::{clean_channel, ephemeral_interaction, unix_timestamp, CmdError},
+    ConnType, DBCONNS,
+};
+
+pub const PAGE_SIZE: usize = 10;
+
+/// Sends a browser for the sessions running in `guild_id`, only visible to the person asking.
+/// Looking up the channels can take a while, so the response is deferred.
+pub async fn show(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    guild_id: GuildId,
+) -> Result<()> {
+    command
+        .create_interaction_response(ctx, |r| {
+            r.kind(DeferredChannelMessageWithSource)
+                .interaction_response_data(|d| d.ephemeral(true))
+        })
+        .await?;
+    let (embed, components) = match render(ctx, guild_id, 0, None).await {
+        Ok(page) => page,
+        Err(err) => return CmdError::ListSessions(err).edit(ctx, command).await,
+    };
+    command
+        .edit_original_interaction_response(ctx, |m| m.set_embed(embed).set_components(components))
+        .await?;
+    Ok(())
+}
+
+fn describe_type(conn_type: &ConnType) -> &'static str {
+    match conn_type {
+        ConnType::ConnectedChannel => "Connected channel",
+        ConnType::EphemeralChannel => "Session channel",
+        ConnType::Thread => "Thread",
+    }
+}
+
+/// One page of sessions, with menus acting on the sessions it lists.
+async fn render(
+    ctx: &Context,
+    guild_id: GuildId,
+    page: usize,
+    note: Option<String>,
+) -> Result<(CreateEmbed, CreateComponents), surrealdb::Error> {
+    let active = channel_info::active(guild_id).await?;
+    let pages = active.len().div_ceil(PAGE_SIZE).max(1);
+    let page = page.min(pages - 1);
+
+    let mut lines = Vec::new();
+    let mut options = Vec::new();
+    for info in active.iter().skip(page * PAGE_SIZE).take(PAGE_SIZE) {
+        let Some(conn) = DBCONNS.lock().await.get(info.channel_id.as_u64()).cloned() else {
+            continue;
+        };
+        let name = match info.channel_id.to_channel(ctx).await.map(|c| c.guild()) {
+            Ok(Some(channel)) => channel.name,
+            _ => info.channel_id.to_string(),
+        };
+        let size = match memory::last_footprint(info.channel_id).await {
+            Some(size) => format!("~{}", format_size(size)),
+            None => "not measured yet".to_string(),
+        };
+        let expires = unix_timestamp(conn.expires_at());
+        let extended = match conn.extensions {
+            0 => String::new(),
+            1 => ", extended once".to_string(),
+            n => format!(", extended {n} times"),
+        };
+        lines.push(format!(
+            "{}\n{}, {}, expires <t:{expires}:R>{extended}",
+            info.describe(),
+            describe_type(&conn.conn_type),
+            size
+        ));
+        options.push((name, info.channel_id));
+    }
+
+    let mut embed = CreateEmbed::default();
+    embed
+        .title(format!("Sessions in this server ({})", active.len()))
+        .description(match lines.is_empty() {
+            true => "There are no sessions running in this server.".to_string(),
+            false => lines.join("\n\n"),
+        })
+        .footer(|f| f.text(format!("Page {} of {pages}", page + 1)));
+    if let Some(note) = note {
+        embed.field("Done", note, false);
+    }
+
+    let mut components = CreateComponents::default();
+    if !options.is_empty() {
+        for (action, placeholder) in [
+            ("export", "Export sessions..."),
+            ("extend", "Extend sessions, restarting their TTL..."),
+            ("clean", "Clean sessions..."),
+        ] {
+            components.create_action_row(|r| {
+                r.create_select_menu(|s| {
+                    s.custom_id(format!("session_browser:{action}:{page}"))
+                        .placeholder(placeholder)
+                        .min_values(1)
+                        .max_values(options.len() as u64)
+                        .options(|o| {
+                            for (name, channel_id) in &options {
+                                o.create_option(|o| o.label(format!("#{name}")).value(channel_id));
+                            }
+                            o
+                        })
+                })
+            });
+        }
+    }
+    components.create_action_row(|r| {
+        r.create_button(|b| {
+            b.custom_id(format!("session_browser:prev:{page}"))
+                .label("Previous")
+                .style(Secondary)
+                .disabled(page == 0)
+        })
+        .create_button(|b| {
+            b.custom_id(format!("session_browser:refresh:{page}"))
+                .label("Refresh")
+                .style(Primary)
+                .emoji('🔄')
+        })
+        .create_button(|b| {
+            b.custom_id(format!("session_browser:next:{page}"))
+                .label("Next")
+                .style(Secondary)
+                .disabled(page + 1 >= pages)
+        })
+    });
+    Ok((embed, components))
+}
+
+pub async fn handle_component(
+    ctx: &Context,
+    event: &MessageComponentInteraction,
+    guild: &GuildId,
+    id: &str,
+    values: &[String],
+) -> Result<()> {
+    if !event
+        .member
+        .as_ref()
+        .and_then(|m| m.permissions)
+        .is_some_and(|p| p.manage_channels())
+    {
+        info!("User tried to manage sessions, but has no permissions");
+        return ephemeral_interaction(
+            ctx,
+            event,
+            "No permissions!",
+            "You need to have `Manage Channels` permission to manage sessions!",
+            Some(false),
+        )
+        .await;
+    }
+
+    let Some((action, page)) = id.split_once(':') else {
+        return Ok(());
+    };
+    let page: usize = page.parse()?;
+    event
+        .create_interaction_response(ctx, |r| r.kind(DeferredUpdateMessage))
+        .await?;
+
+    // Only act on sessions of this server, whatever the menu claims.
+    let active: Vec<ChannelInfo> = channel_info::active(*guild).await.unwrap_or_default();
+    let selected: Vec<ChannelId> = values
+        .iter()
+        .filter_map(|v| v.parse().ok().map(ChannelId))
+        .filter(|c| active.iter().any(|info| info.channel_id == *c))
+        .collect();
+
+    let (page, note) = match action {
+        "prev" => (page.saturating_sub(1), None),
+        "next" => (page + 1, None),
+        "refresh" => (page, None),
+        "export" => (page, Some(export(ctx, event, &selected).await)),
+        "extend" => (page, Some(extend(&selected).await)),
+        "clean" => (page, Some(clean(ctx, &selected).await)),
+        _ => return Ok(()),
+    };
+
+    let (embed, components) = match render(ctx, *guild, page, note).await {
+        Ok(page) => page,
+        Err(err) => return CmdError::ListSessions(err).edit(ctx, event).await,
+    };
+    event
+        .edit_original_interaction_response(ctx, |m| m.set_embed(embed).set_components(components))
+        .await?;
+    Ok(())
+}
+
+/// Sends exports of the sessions to the person asking.
+async fn export(
+    ctx: &Context,
+    event: &MessageComponentInteraction,
+    selected: &[ChannelId],
+) -> String {
+    let mut exported = 0;
+    for channel_id in selected {
+        let Some(conn) = DBCONNS.lock().await.get(channel_id.as_u64()).cloned() else {
+            continue;
+        };
+        let export = match conn.export().await {
+            Ok(export) => export,
+            Err(err) => {
+                error!(error = %err, channel_id = %channel_id, "Failed to export session");
+                continue;
+            }
+        };
+        let sent = event
+            .create_followup_message(ctx, |m| {
+                m.ephemeral(true)
+                    .content(format!("Export of <#{channel_id}>"))
+                    .add_file(AttachmentType::Bytes {
+                        data: Cow::Owned(export),
+                        filename: format!("{channel_id}.surql"),
+                    })
+            })
+            .await;
+        match sent {
+            Ok(_) => exported += 1,
+            Err(err) => {
+                error!(error = %err, channel_id = %channel_id, "Failed to send session export")
+            }
+        }
+    }
+    format!("Exported {exported} of {} sessions", selected.len())
+}
+
+async fn extend(selected: &[ChannelId]) -> String {
+    let mut conns = DBCONNS.lock().await;
+    let mut extended = 0;
+    for channel_id in selected {
+        if let Some(conn) = conns.get_mut(channel_id.as_u64()) {
+            conn.extend();
+            extended += 1;
+        }
+    }
+    format!("Restarted the TTL of {extended} sessions")
+}
+
+async fn clean(ctx: &Context, selected: &[ChannelId]) -> String {
+    let mut cleaned = 0;
+    for channel_id in selected {
+        match channel_id.to_channel(ctx).await.map(|c| c.guild()) {
+            Ok(Some(channel)) => {
+                clean_channel(channel, ctx).await;
+                cleaned += 1;
+            }
+            _ => warn!(channel_id = %channel_id, "Failed to get channel to clean"),
+        }
+    }
+    format!("Cleaned {cleaned} sessions")
+}
@@ -1,13 +1,18 @@
+use std::{hash::Hash, str::FromStr};
+
 use serde::{Deserialize, Serialize};
 use serenity::{
-    builder::CreateApplicationCommand,
+    builder::{CreateApplicationCommand, CreateApplicationCommandOption},
     model::prelude::{
         application_command::ApplicationCommandInteraction, command::CommandOptionType, ChannelId,
         ChannelType, GuildId,
     },
 };
+use surrealdb::dbs::capabilities::{Capabilities, Targets};
 use tokio::time::Duration;
 
+use crate::{channel_info::SessionLimits, quotas::Quotas};
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Config {
     pub guild_id: GuildId,
@@ -17,6 +22,184 @@ pub struct Config {
     pub timeout: Duration,
     pub pretty: bool,
     pub json: bool,
+    #[serde(default)]
+    pub engine: Engine,
+    #[serde(default)]
+    pub capabilities: CapabilityPolicy,
+    #[serde(default)]
+    pub quotas: Quotas,
+    #[serde(default)]
+    pub session_limits: SessionLimits,
+    /// How long before a session expires its channel is warned, zero for no warning.
+    #[serde(default = "default_ttl_warning")]
+    pub ttl_warning: Duration,
+    /// Longest a session can live however often it's used or extended, zero for no limit.
+    #[serde(default)]
+    pub max_lifetime: Duration,
+}
+
+fn default_ttl_warning() -> Duration {
+    Duration::from_secs(5 * 60)
+}
+
+/// Storage engine backing a session's database instance.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Engine {
+    #[default]
+    Memory,
+    RocksDb,
+    SurrealKv,
+    /// SurrealKV with versioning enabled, which allows `VERSION` time-travel queries.
+    SurrealKvVersioned,
+}
+
+impl Engine {
+    pub const ALL: [Engine; 4] = [
+        Engine::Memory,
+        Engine::RocksDb,
+        Engine::SurrealKv,
+        Engine::SurrealKvVersioned,
+    ];
+
+    pub fn from_option(value: &str) -> Option<Engine> {
+        match value {
+            "memory" => Some(Engine::Memory),
+            "rocksdb" => Some(Engine::RocksDb),
+            "surrealkv" => Some(Engine::SurrealKv),
+            "surrealkv_versioned" => Some(Engine::SurrealKvVersioned),
+            _ => None,
+        }
+    }
+
+    pub fn option_value(&self) -> &'static str {
+        match self {
+            Engine::Memory => "memory",
+            Engine::RocksDb => "rocksdb",
+            Engine::SurrealKv => "surrealkv",
+            Engine::SurrealKvVersioned => "surrealkv_versioned",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Engine::Memory => "In-memory",
+            Engine::RocksDb => "RocksDB (on disk)",
+            Engine::SurrealKv => "SurrealKV (on disk)",
+            Engine::SurrealKvVersioned => "Versioned SurrealKV (on disk, time travel)",
+        }
+    }
+
+    /// Whether the database lives in a managed directory on disk rather than in memory.
+    pub fn is_on_disk(&self) -> bool {
+        !matches!(self, Engine::Memory)
+    }
+
+    pub fn register_option(
+        option: &mut CreateApplicationCommandOption,
+    ) -> &mut CreateApplicationCommandOption {
+        option
+            .name("engine")
+            .description("Storage engine for database instances")
+            .kind(CommandOptionType::String);
+        for engine in Engine::ALL {
+            option.add_string_choice(engine.label(), engine.option_value());
+        }
+        option
+    }
+}
+
+/// What queries in a server's sessions are allowed to do, applied when their database instances are created.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct CapabilityPolicy {
+    /// Whether embedded JavaScript functions can run.
+    pub scripting: bool,
+    /// Whether queries can run without being signed in, e.g. after `/auth invalidate`.
+    pub guest_access: bool,
+    /// Function targets like `string::*` or `http::get`, `*` standing for all of them.
+    pub allow_functions: Vec<String>,
+    pub deny_functions: Vec<String>,
+    /// Network targets like `surrealdb.com` or `10.0.0.0/8`, `*` standing for all of them.
+    pub allow_net: Vec<String>,
+    pub deny_net: Vec<String>,
+}
+
+/// SurrealDB's own defaults.
+impl Default for CapabilityPolicy {
+    fn default() -> Self {
+        CapabilityPolicy {
+            scripting: false,
+            guest_access: false,
+            allow_functions: vec!["*".to_string()],
+            deny_functions: Vec::new(),
+            allow_net: Vec::new(),
+            deny_net: Vec::new(),
+        }
+    }
+}
+
+impl CapabilityPolicy {
+    pub fn build(&self) -> Result<Capabilities, anyhow::Error> {
+        Ok(Capabilities::default()
+            .with_scripting(self.scripting)
+            .with_guest_access(self.guest_access)
+            .with_functions(Self::targets(&self.allow_functions)?)
+            .without_functions(Self::targets(&self.deny_functions)?)
+            .with_network_targets(Self::targets(&self.allow_net)?)
+            .without_network_targets(Self::targets(&self.deny_net)?))
+    }
+
+    fn targets<T>(targets: &[String]) -> Result<Targets<T>, anyhow::Error>
+    where
+        T: FromStr + Hash + Eq,
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        Ok(match targets {
+            [] => Targets::None,
+            [all] if all == "*" => Targets::All,
+            targets => Targets::Some(
+                targets
+                    .iter()
+                    .map(|t| t.parse())
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+
+    /// Reads a comma separated list of targets, where `none` (or nothing) stands for an empty list.
+    pub fn parse_targets(value: &str) -> Vec<String> {
+        let targets: Vec<String> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect();
+        match targets.as_slice() {
+            [none] if none.eq_ignore_ascii_case("none") => Vec::new(),
+            _ => targets,
+        }
+    }
+
+    pub fn format_targets(targets: &[String]) -> String {
+        if targets.is_empty() {
+            "none".to_string()
+        } else {
+            targets.join(", ")
+        }
+    }
+
+    /// Summary for the server config panel.
+    pub fn describe(&self) -> String {
+        format!(
+            "Scripting: {}\nGuest access: {}\nFunctions: `{}`, except `{}`\nNetwork targets: `{}`, except `{}`",
+            if self.scripting { "allowed" } else { "denied" },
+            if self.guest_access { "allowed" } else { "denied" },
+            Self::format_targets(&self.allow_functions),
+            Self::format_targets(&self.deny_functions),
+            Self::format_targets(&self.allow_net),
+            Self::format_targets(&self.deny_net),
+        )
+    }
 }
 
 impl Config {
@@ -34,15 +217,66 @@ impl Config {
         if let Some(timeout) = to_add.timeout {
             self.timeout = timeout;
         }
+        if let Some(ttl_warning) = to_add.ttl_warning {
+            self.ttl_warning = ttl_warning;
+        }
+        if let Some(max_lifetime) = to_add.max_lifetime {
+            self.max_lifetime = max_lifetime;
+        }
         if let Some(pretty) = to_add.pretty {
             self.pretty = pretty;
         }
         if let Some(json) = to_add.json {
             self.json = json;
         }
+        if let Some(engine) = to_add.engine {
+            self.engine = engine;
+        }
+        if let Some(scripting) = to_add.scripting {
+            self.capabilities.scripting = scripting;
+        }
+        if let Some(guest_access) = to_add.guest_access {
+            self.capabilities.guest_access = guest_access;
+        }
+        if let Some(allow_functions) = to_add.allow_functions {
+            self.capabilities.allow_functions = allow_functions;
+        }
+        if let Some(deny_functions) = to_add.deny_functions {
+            self.capabilities.deny_functions = deny_functions;
+        }
+        if let Some(allow_net) = to_add.allow_net {
+            self.capabilities.allow_net = allow_net;
+        }
+        if let Some(deny_net) = to_add.deny_net {
+            self.capabilities.deny_net = deny_net;
+        }
+        if let Some(max_attachment_size) = to_add.max_attachment_size {
+            self.quotas.max_attachment_size = max_attachment_size;
+        }
+        if let Some(max_export_size) = to_add.max_export_size {
+            self.quotas.max_export_size = max_export_size;
+        }
+        if let Some(max_records) = to_add.max_records {
+            self.quotas.max_records = max_records;
+        }
+        if let Some(max_statements) = to_add.max_statements {
+            self.quotas.max_statements = max_statements;
+        }
+        if let Some(per_user) = to_add.max_sessions_per_user {
+            self.session_limits.per_user = per_user;
+        }
+        if let Some(per_guild) = to_add.max_sessions_per_guild {
+            self.session_limits.per_guild = per_guild;
+        }
+        if let Some(archive_oldest) = to_add.archive_oldest {
+            self.session_limits.archive_oldest = archive_oldest;
+        }
     }
 
     pub fn from_builder(builder: ConfigBuilder) -> Option<Config> {
+        let defaults = CapabilityPolicy::default();
+        let quotas = Quotas::default();
+        let limits = SessionLimits::default();
         Some(Config {
             guild_id: builder.guild_id?,
             active_channel: builder.active_channel?,
@@ -51,6 +285,30 @@ impl Config {
             timeout: builder.timeout?,
             pretty: builder.pretty?,
             json: builder.json?,
+            engine: builder.engine.unwrap_or_default(),
+            capabilities: CapabilityPolicy {
+                scripting: builder.scripting.unwrap_or(defaults.scripting),
+                guest_access: builder.guest_access.unwrap_or(defaults.guest_access),
+                allow_functions: builder.allow_functions.unwrap_or(defaults.allow_functions),
+                deny_functions: builder.deny_functions.unwrap_or(defaults.deny_functions),
+                allow_net: builder.allow_net.unwrap_or(defaults.allow_net),
+                deny_net: builder.deny_net.unwrap_or(defaults.deny_net),
+            },
+            quotas: Quotas {
+                max_attachment_size: builder
+                    .max_attachment_size
+                    .unwrap_or(quotas.max_attachment_size),
+                max_export_size: builder.max_export_size.unwrap_or(quotas.max_export_size),
+                max_records: builder.max_records.unwrap_or(quotas.max_records),
+                max_statements: builder.max_statements.unwrap_or(quotas.max_statements),
+            },
+            session_limits: SessionLimits {
+                per_user: builder.max_sessions_per_user.unwrap_or(limits.per_user),
+                per_guild: builder.max_sessions_per_guild.unwrap_or(limits.per_guild),
+                archive_oldest: builder.archive_oldest.unwrap_or(limits.archive_oldest),
+            },
+            ttl_warning: builder.ttl_warning.unwrap_or_else(default_ttl_warning),
+            max_lifetime: builder.max_lifetime.unwrap_or_default(),
         })
     }
 
@@ -63,6 +321,12 @@ impl Config {
             timeout: Duration::from_secs(8),
             pretty: true,
             json: false,
+            engine: Engine::Memory,
+            capabilities: CapabilityPolicy::default(),
+            quotas: Quotas::default(),
+            session_limits: SessionLimits::default(),
+            ttl_warning: default_ttl_warning(),
+            max_lifetime: Duration::ZERO,
         }
     }
 }
@@ -74,8 +338,24 @@ pub struct ConfigBuilder {
     pub archive_channel: Option<ChannelId>,
     pub ttl: Option<Duration>,
     pub timeout: Option<Duration>,
+    pub ttl_warning: Option<Duration>,
+    pub max_lifetime: Option<Duration>,
     pub pretty: Option<bool>,
     pub json: Option<bool>,
+    pub engine: Option<Engine>,
+    pub scripting: Option<bool>,
+    pub guest_access: Option<bool>,
+    pub allow_functions: Option<Vec<String>>,
+    pub deny_functions: Option<Vec<String>>,
+    pub allow_net: Option<Vec<String>>,
+    pub deny_net: Option<Vec<String>>,
+    pub max_attachment_size: Option<u64>,
+    pub max_export_size: Option<u64>,
+    pub max_records: Option<u64>,
+    pub max_statements: Option<u64>,
+    pub max_sessions_per_user: Option<u64>,
+    pub max_sessions_per_guild: Option<u64>,
+    pub archive_oldest: Option<bool>,
 }
 
 impl ConfigBuilder {
@@ -101,6 +381,16 @@ impl ConfigBuilder {
                         option.value.clone().unwrap().as_u64().unwrap(),
                     ))
                 }
+                "ttl_warning" => {
+                    acc.ttl_warning = Some(Duration::from_secs(
+                        option.value.clone().unwrap().as_u64().unwrap(),
+                    ))
+                }
+                "max_lifetime" => {
+                    acc.max_lifetime = Some(Duration::from_secs(
+                        option.value.clone().unwrap().as_u64().unwrap(),
+                    ))
+                }
                 "timeout" => {
                     acc.timeout = Some(Duration::from_secs(
                         option.value.clone().unwrap().as_u64().unwrap(),
@@ -108,6 +398,51 @@ impl ConfigBuilder {
                 }
                 "pretty" => acc.pretty = Some(option.value.clone().unwrap().as_bool().unwrap()),
                 "json" => acc.json = Some(option.value.clone().unwrap().as_bool().unwrap()),
+                "engine" => {
+                    acc.engine = Engine::from_option(option.value.unwrap().as_str().unwrap())
+                }
+                "scripting" => {
+                    acc.scripting = Some(option.value.clone().unwrap().as_bool().unwrap())
+                }
+                "guest_access" => {
+                    acc.guest_access = Some(option.value.clone().unwrap().as_bool().unwrap())
+                }
+                "max_attachment_mb" => {
+                    acc.max_attachment_size =
+                        Some(option.value.clone().unwrap().as_u64().unwrap() * 1024 * 1024)
+                }
+                "max_export_mb" => {
+                    acc.max_export_size =
+                        Some(option.value.clone().unwrap().as_u64().unwrap() * 1024 * 1024)
+                }
+                "max_records" => {
+                    acc.max_records = Some(option.value.clone().unwrap().as_u64().unwrap())
+                }
+                "max_statements" => {
+                    acc.max_statements = Some(option.value.clone().unwrap().as_u64().unwrap())
+                }
+                "max_sessions_per_user" => {
+                    acc.max_sessions_per_user =
+                        Some(option.value.clone().unwrap().as_u64().unwrap())
+                }
+                "max_sessions_per_guild" => {
+                    acc.max_sessions_per_guild =
+                        Some(option.value.clone().unwrap().as_u64().unwrap())
+                }
+                "archive_oldest" => {
+                    acc.archive_oldest = Some(option.value.clone().unwrap().as_bool().unwrap())
+                }
+                "allow_functions" | "deny_functions" | "allow_net" | "deny_net" => {
+                    let targets = CapabilityPolicy::parse_targets(
+                        option.value.as_ref().unwrap().as_str().unwrap(),
+                    );
+                    match option.name.as_str() {
+                        "allow_functions" => acc.allow_functions = Some(targets),
+                        "deny_functions" => acc.deny_functions = Some(targets),
+                        "allow_net" => acc.allow_net = Some(targets),
+                        _ => acc.deny_net = Some(targets),
+                    }
+                }
                 _ => {}
             }
         }
@@ -121,9 +456,25 @@ impl ConfigBuilder {
             active_channel: None,
             archive_channel: None,
             ttl: None,
+            ttl_warning: None,
+            max_lifetime: None,
             timeout: None,
             pretty: None,
             json: None,
+            engine: None,
+            scripting: None,
+            guest_access: None,
+            allow_functions: None,
+            deny_functions: None,
+            allow_net: None,
+            deny_net: None,
+            max_attachment_size: None,
+            max_export_size: None,
+            max_records: None,
+            max_statements: None,
+            max_sessions_per_user: None,
+            max_sessions_per_guild: None,
+            archive_oldest: None,
         }
     }
 }
@@ -178,4 +529,128 @@ pub fn register_options(
                 .default_option(false)
                 .required(req)
         })
+        .create_option(|option| Engine::register_option(option).required(false))
+        .create_option(|option| {
+            option
+                .name("scripting")
+                .description("Whether queries can run embedded JavaScript functions")
+                .kind(CommandOptionType::Boolean)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("guest_access")
+                .description("Whether queries can run without being signed in")
+                .kind(CommandOptionType::Boolean)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("allow_functions")
+                .description("Functions queries can call, comma separated, * for all or none")
+                .kind(CommandOptionType::String)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("deny_functions")
+                .description("Functions queries can't call, comma separated, * for all or none")
+                .kind(CommandOptionType::String)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("allow_net")
+                .description(
+                    "Network targets functions can reach, comma separated, * for all or none",
+                )
+                .kind(CommandOptionType::String)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("deny_net")
+                .description(
+                    "Network targets functions can't reach, comma separated, * for all or none",
+                )
+                .kind(CommandOptionType::String)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("max_attachment_mb")
+                .description(
+                    "Largest file in MiB that can be loaded into a session, 0 for no limit",
+                )
+                .kind(CommandOptionType::Integer)
+                .min_int_value(0)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("max_export_mb")
+                .description(
+                    "Largest a session's data can grow in MiB when exported, 0 for no limit",
+                )
+                .kind(CommandOptionType::Integer)
+                .min_int_value(0)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("max_records")
+                .description("Most records a session can hold, 0 for no limit")
+                .kind(CommandOptionType::Integer)
+                .min_int_value(0)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("max_statements")
+                .description("Most statements a single query can have, 0 for no limit")
+                .kind(CommandOptionType::Integer)
+                .min_int_value(0)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("max_sessions_per_user")
+                .description("Most sessions a user can have running at once, 0 for no limit")
+                .kind(CommandOptionType::Integer)
+                .min_int_value(0)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("max_sessions_per_guild")
+                .description("Most sessions that can run at once in this server, 0 for no limit")
+                .kind(CommandOptionType::Integer)
+                .min_int_value(0)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("archive_oldest")
+                .description(
+                    "Archive the user's oldest session when they hit a limit, instead of refusing",
+                )
+                .kind(CommandOptionType::Boolean)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("ttl_warning")
+                .description("How long before a session expires to warn its channel in seconds, 0 for no warning")
+                .kind(CommandOptionType::Integer)
+                .min_int_value(0)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("max_lifetime")
+                .description("Longest a session can live in seconds, however it's used or extended, 0 for no limit")
+                .kind(CommandOptionType::Integer)
+                .min_int_value(0)
+                .required(false)
+        })
 }
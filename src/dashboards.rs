@@ -0,0 +1,222 @@
+use std::collections::BTreeSet;
+
+use futures::{stream::select_all, StreamExt};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    http::Http,
+    model::{
+        prelude::{component::ButtonStyle::Danger, ChannelId, MessageId, UserId},
+        Timestamp,
+    },
+};
+use surrealdb::sql;
+use tokio::time::{sleep, timeout, Duration};
+use tracing::Instrument;
+
+use crate::{process, utils::system_message, Conn, DBCONNS};
+
+pub const MAX_DASHBOARDS: usize = 3;
+/// Refresh interval for dashboards without one, when no table to watch could be found in the query.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+pub const MIN_INTERVAL: Duration = Duration::from_secs(10);
+/// How long to wait for more changes before refreshing a live dashboard.
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// A query whose result is kept up to date in a pinned message.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Dashboard {
+    pub message_id: MessageId,
+    pub query: String,
+    /// Refresh on a timer rather than when the queried tables change.
+    pub interval: Option<Duration>,
+    /// Refreshes run as the identity the creator picked with `/auth`, older saved dashboards have none and run as root.
+    #[serde(default)]
+    pub creator: UserId,
+}
+
+/// Tables read by the `SELECT` statements of a query.
+fn watched_tables(query: &str) -> BTreeSet<String> {
+    let Ok(query) = sql::parse(query) else {
+        return BTreeSet::new();
+    };
+    query
+        .iter()
+        .filter_map(|s| match s {
+            sql::Statement::Select(select) => Some(select.what.0.iter()),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|what| match what {
+            sql::Value::Table(table) => Some(table.0.clone()),
+            sql::Value::Thing(thing) => Some(thing.tb.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Posts a placeholder message for a new dashboard and pins it.
+pub async fn create(
+    http: impl AsRef<Http>,
+    channel: ChannelId,
+    query: &str,
+) -> Result<MessageId, anyhow::Error> {
+    let message = channel
+        .send_message(&http, |m| {
+            m.embed(|e| {
+                e.title("Dashboard")
+                    .description(format!("```sql\n{query}\n```\nLoading..."))
+            })
+        })
+        .await?;
+    channel
+        .edit_message(&http, message.id, |m| {
+            m.components(|c| {
+                c.create_action_row(|r| {
+                    r.create_button(|b| {
+                        b.custom_id(format!(
+                            "configurable_session:remove_dashboard:{}",
+                            message.id
+                        ))
+                        .label("Remove dashboard")
+                        .style(Danger)
+                        .emoji('🗑')
+                    })
+                })
+            })
+        })
+        .await?;
+    if let Err(err) = channel.pin(&http, message.id).await {
+        warn!(error = %err, "Failed to pin dashboard");
+    }
+    Ok(message.id)
+}
+
+/// Runs the dashboard query as its creator and puts its result in the dashboard message.
+/// Returns false once the session is gone, or once the dashboard is removed as its creator's identity can't be used anymore.
+async fn refresh(http: &Http, channel: ChannelId, dashboard: &Dashboard) -> bool {
+    let Some(conn) = DBCONNS.lock().await.get(channel.as_u64()).cloned() else {
+        return false;
+    };
+    let Ok(handle) = conn.handle(Some(dashboard.creator)).await else {
+        conn.dashboards.lock().await.remove(&dashboard.message_id);
+        system_message(
+            http,
+            &channel,
+            "Dashboard stopped",
+            "The identity its creator picked with `/auth` can't be used anymore, the dashboard has been removed.",
+            Some(false),
+            Some(format!("<@{}>", dashboard.creator)),
+            None,
+        )
+        .await
+        .ok();
+        channel
+            .delete_message(http, dashboard.message_id)
+            .await
+            .ok();
+        return false;
+    };
+    let result = handle.query(&dashboard.query).await;
+    let reply = match process(conn.pretty, conn.json, result) {
+        Ok(r) => r,
+        Err(e) => e.to_string(),
+    };
+    let language = if conn.json { "json" } else { "sql" };
+    let shown = if reply.len() > 3000 {
+        let mut end = 3000;
+        while !reply.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!(
+            "```{language}\n{}\n```\n:information_source: Result was too long and has been truncated",
+            &reply[..end]
+        )
+    } else {
+        format!("```{language}\n{reply}\n```")
+    };
+    if let Err(err) = channel
+        .edit_message(http, dashboard.message_id, |m| {
+            m.embed(|e| {
+                e.title("Dashboard")
+                    .description(format!("```sql\n{}\n```\n{shown}", dashboard.query))
+                    .field(
+                        "Refreshes",
+                        match dashboard.interval {
+                            Some(interval) => {
+                                format!("every {}", humantime::format_duration(interval))
+                            }
+                            None => "when the data changes".to_string(),
+                        },
+                        true,
+                    )
+                    .timestamp(Timestamp::now())
+            })
+        })
+        .await
+    {
+        warn!(error = %err, "Failed to refresh dashboard");
+    }
+    true
+}
+
+/// Keeps a dashboard up to date in the background until it's removed or the session ends.
+/// Returns false without starting it if the session already has [`MAX_DASHBOARDS`].
+pub async fn start(
+    http: impl AsRef<Http> + Send + Sync + 'static,
+    channel: ChannelId,
+    conn: &Conn,
+    dashboard: Dashboard,
+) -> bool {
+    let mut dashboards = conn.dashboards.lock().await;
+    if dashboards.len() >= MAX_DASHBOARDS {
+        return false;
+    }
+    let tables = watched_tables(&dashboard.query);
+    let db = conn.root().await;
+    let id = dashboard.message_id;
+    let spec = dashboard.clone();
+    let task = tokio::spawn(
+        async move {
+            let http = http.as_ref();
+            if !refresh(http, channel, &dashboard).await {
+                return;
+            }
+
+            let mut streams = Vec::new();
+            if dashboard.interval.is_none() {
+                for table in &tables {
+                    let live = format!("LIVE SELECT * FROM {}", sql::Table::from(table.as_str()));
+                    let stream = match db.query(live).await {
+                        Ok(mut response) => response.stream(0),
+                        Err(err) => Err(err),
+                    };
+                    match stream {
+                        Ok(stream) => streams.push(stream),
+                        Err(err) => warn!(error = %err, table, "Failed to watch table"),
+                    }
+                }
+            }
+
+            if streams.is_empty() {
+                let interval = dashboard.interval.unwrap_or(DEFAULT_INTERVAL);
+                loop {
+                    sleep(interval).await;
+                    if !refresh(http, channel, &dashboard).await {
+                        return;
+                    }
+                }
+            }
+
+            let mut changes = select_all(streams);
+            while changes.next().await.is_some() {
+                while let Ok(Some(_)) = timeout(DEBOUNCE, changes.next()).await {}
+                if !refresh(http, channel, &dashboard).await {
+                    return;
+                }
+            }
+        }
+        .in_current_span(),
+    );
+    dashboards.insert(id, (spec, task.abort_handle()));
+    true
+}
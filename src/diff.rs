@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use surrealdb::sql;
+
+use crate::session_db::Handle;
+
+/// Schema and contents of every table in a database, as needed to compare two databases.
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    tables: BTreeMap<String, Table>,
+}
+
+#[derive(Debug, Default)]
+struct Table {
+    definition: String,
+    fields: BTreeMap<String, String>,
+    indexes: BTreeMap<String, String>,
+    records: BTreeMap<String, sql::Value>,
+}
+
+/// Added, removed and changed counts for one kind of item.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Counts {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+}
+
+impl Counts {
+    pub fn is_empty(&self) -> bool {
+        self.added == 0 && self.removed == 0 && self.changed == 0
+    }
+}
+
+impl std::fmt::Display for Counts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`+{}` `-{}` `~{}`",
+            self.added, self.removed, self.changed
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Diff {
+    pub tables: Counts,
+    pub fields: Counts,
+    pub indexes: Counts,
+    pub records: Counts,
+    /// Line by line report in the style of a unified diff.
+    pub report: String,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+            && self.fields.is_empty()
+            && self.indexes.is_empty()
+            && self.records.is_empty()
+    }
+}
+
+fn object(value: sql::Value) -> BTreeMap<String, sql::Value> {
+    match value {
+        sql::Value::Object(object) => object.0,
+        _ => BTreeMap::new(),
+    }
+}
+
+fn definitions(value: Option<sql::Value>) -> BTreeMap<String, String> {
+    value
+        .map(object)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, definition)| (name, definition.as_raw_string()))
+        .collect()
+}
+
+/// Reads the schema and records of the current database of `db`.
+pub async fn snapshot(db: &Handle) -> Result<Snapshot, anyhow::Error> {
+    let info = db.query("INFO FOR DB").await?.take(0)?;
+    let mut snapshot = Snapshot::default();
+    for (name, definition) in definitions(object(info).remove("tables")) {
+        let table = sql::Ident::from(name.as_str());
+        let mut response = db
+            .query(format!("INFO FOR TABLE {table}; SELECT * FROM {table}"))
+            .await?;
+        let mut info = object(response.take(0)?);
+        let records = match response.take(1)? {
+            sql::Value::Array(records) => records
+                .0
+                .into_iter()
+                .map(|record| {
+                    let id = match &record {
+                        sql::Value::Object(object) => object
+                            .get("id")
+                            .map(|id| id.to_string())
+                            .unwrap_or_default(),
+                        _ => String::new(),
+                    };
+                    (id, record)
+                })
+                .collect(),
+            _ => BTreeMap::new(),
+        };
+        snapshot.tables.insert(
+            name,
+            Table {
+                definition,
+                fields: definitions(info.remove("fields")),
+                indexes: definitions(info.remove("indexes")),
+                records,
+            },
+        );
+    }
+    Ok(snapshot)
+}
+
+/// Compares two maps, writing a line to `report` for each difference, with names prefixed by `prefix`.
+fn compare<T: PartialEq + std::fmt::Display>(
+    prefix: &str,
+    before: &BTreeMap<String, T>,
+    after: &BTreeMap<String, T>,
+    report: &mut String,
+) -> Counts {
+    let mut counts = Counts::default();
+    for (name, old) in before {
+        match after.get(name) {
+            None => {
+                counts.removed += 1;
+                writeln!(report, "- {prefix}{name}: {old}").ok();
+            }
+            Some(new) if new != old => {
+                counts.changed += 1;
+                writeln!(report, "- {prefix}{name}: {old}").ok();
+                writeln!(report, "+ {prefix}{name}: {new}").ok();
+            }
+            Some(_) => {}
+        }
+    }
+    for (name, new) in after {
+        if !before.contains_key(name) {
+            counts.added += 1;
+            writeln!(report, "+ {prefix}{name}: {new}").ok();
+        }
+    }
+    counts
+}
+
+/// Lists what changed to go from `before` to `after`.
+pub fn diff(before: &Snapshot, after: &Snapshot) -> Diff {
+    let mut diff = Diff::default();
+    let empty = Table::default();
+
+    let before_tables = before
+        .tables
+        .iter()
+        .map(|(name, table)| (name.clone(), table.definition.clone()))
+        .collect();
+    let after_tables = after
+        .tables
+        .iter()
+        .map(|(name, table)| (name.clone(), table.definition.clone()))
+        .collect();
+    diff.tables = compare("table ", &before_tables, &after_tables, &mut diff.report);
+
+    let names: std::collections::BTreeSet<_> =
+        before.tables.keys().chain(after.tables.keys()).collect();
+    for name in names {
+        let old = before.tables.get(name).unwrap_or(&empty);
+        let new = after.tables.get(name).unwrap_or(&empty);
+        let fields = compare(
+            &format!("field {name}."),
+            &old.fields,
+            &new.fields,
+            &mut diff.report,
+        );
+        let indexes = compare(
+            &format!("index {name}."),
+            &old.indexes,
+            &new.indexes,
+            &mut diff.report,
+        );
+        let records = compare("record ", &old.records, &new.records, &mut diff.report);
+        for (total, counts) in [
+            (&mut diff.fields, fields),
+            (&mut diff.indexes, indexes),
+            (&mut diff.records, records),
+        ] {
+            total.added += counts.added;
+            total.removed += counts.removed;
+            total.changed += counts.changed;
+        }
+    }
+    diff
+}
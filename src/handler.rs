@@ -9,9 +9,15 @@ use tracing::Instrument;
 use tracing::Level;
 
 use crate::commands;
-use crate::process;
+use crate::discord_vars;
+use crate::has_writes;
+use crate::history::{self, HistoryEntry};
+use crate::live;
+use crate::persistence;
+use crate::process_outcome;
 use crate::utils::ephemeral_interaction;
 use crate::utils::respond;
+use crate::utils::CmdError;
 use crate::DBCONNS;
 
 fn validate_msg(msg: &Message) -> bool {
@@ -47,15 +53,84 @@ impl EventHandler for Handler {
             }
         };
         if validate_msg(&msg) {
-            let result = conn.db.query(&msg.content).await;
-            let reply = match process(conn.pretty, conn.json, result) {
-                Ok(r) => r,
-                Err(e) => e.to_string(),
+            let checked = match conn.check_query(&msg.content).await {
+                Ok(()) => conn.handle(Some(msg.author.id)).await,
+                Err(exceeded) => Err(CmdError::QuotaExceeded(exceeded)),
             };
+            let handle = match checked {
+                Ok(handle) => handle,
+                Err(err) => {
+                    if let Err(err) = err.send(&ctx, &msg.channel_id).await {
+                        error!(error = %err, "Failed to send query error");
+                    }
+                    return;
+                }
+            };
+            let snapshot = if conn.undo && has_writes(&msg.content) {
+                let before = conn.writes();
+                match conn.export().await {
+                    Ok(export) => Some((before, export)),
+                    Err(err) => {
+                        error!(error = %err, "Failed to snapshot session for undo");
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            let now = Instant::now();
+            let mut result = handle
+                .query_with(
+                    &msg.content,
+                    discord_vars(&msg.author, msg.channel_id, msg.id),
+                )
+                .await;
+            let elapsed = now.elapsed();
+            let undoable = match snapshot {
+                Some((before, export)) => {
+                    conn.set_undo(msg.id, msg.author.id, export, before).await
+                }
+                None => false,
+            };
+            conn.record_version().await;
+            conn.record_query();
+            let streams = match result.as_mut() {
+                Ok(response) => live::take_streams(&msg.content, response),
+                Err(_) => Vec::new(),
+            };
+            let (reply, error) = process_outcome(conn.pretty, conn.json, result);
+            history::record(HistoryEntry::new(
+                msg.channel_id,
+                msg.author.id,
+                msg.content.clone(),
+                None,
+                elapsed,
+                error,
+            ));
 
-            respond(reply, ctx, msg.clone(), &conn, msg.channel_id)
-                .await
-                .unwrap();
+            respond(
+                reply,
+                ctx.clone(),
+                msg.clone(),
+                &conn,
+                msg.channel_id,
+                undoable,
+            )
+            .await
+            .unwrap();
+            if has_writes(&msg.content) {
+                if let Err(exceeded) = conn.check_usage().await {
+                    if let Err(err) = CmdError::QuotaExceeded(exceeded)
+                        .send(&ctx, &msg.channel_id)
+                        .await
+                    {
+                        error!(error = %err, "Failed to send quota error");
+                    }
+                }
+            }
+            if let Err(err) = live::watch(ctx, msg.channel_id, &conn, streams).await {
+                error!(error = %err, "Failed to start live queries");
+            }
         }
     }
 
@@ -70,6 +145,8 @@ impl EventHandler for Handler {
                 error!(error = %why, guild_id = %guild.id, "Failed to register commands.");
             }
         }
+
+        persistence::restore_all(&ctx).await;
     }
 
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
@@ -88,7 +165,7 @@ impl EventHandler for Handler {
                 async {
                     trace!(command = ?command, "received command interaction");
                     let res = match command.data.name.as_str() {
-                        // "auth" => commands::auth::run(&command, ctx.clone()).await,
+                        "auth" => commands::auth::run(&command, ctx.clone()).await,
                         "create" => commands::create::run(&command, ctx.clone()).await,
                         "configure" => commands::configure::run(&command, ctx.clone()).await,
                         "share" => commands::share::run(&command, ctx.clone()).await,
@@ -106,6 +183,20 @@ impl EventHandler for Handler {
                         "connect" => commands::connect::run(&command, ctx.clone()).await,
                         "export" => commands::export::run(&command, ctx.clone()).await,
                         "stats" => commands::stats::run(&command, ctx.clone()).await,
+                        "checkpoint" => commands::checkpoint::run(&command, ctx.clone()).await,
+                        "rollback" => commands::rollback::run(&command, ctx.clone()).await,
+                        "fork" => commands::fork::run(&command, ctx.clone()).await,
+                        "diff" => commands::diff::run(&command, ctx.clone()).await,
+                        "dashboard" => commands::dashboard::run(&command, ctx.clone()).await,
+                        "schedule" => commands::schedule::run(&command, ctx.clone()).await,
+                        "param" => commands::param::run(&command, ctx.clone()).await,
+                        "use" => commands::r#use::run(&command, ctx.clone()).await,
+                        "session" => commands::session::run(&command, ctx.clone()).await,
+                        "sessions" => commands::sessions::run(&command, ctx.clone()).await,
+                        "history" => commands::history::run(&command, ctx.clone()).await,
+                        "maintenance" => {
+                            commands::maintenance::run(&command, ctx.clone()).await
+                        }
                         _ => {
                             warn!(command_name = %command.data.name, command_options = ?command.data.options, "unknown command received");
                             ephemeral_interaction(&ctx, &command, "Unknown command", "Command is currently not implemented", Some(false)).await
@@ -145,6 +236,26 @@ impl EventHandler for Handler {
                             )
                             .await
                         }
+                        Some(("session_browser", id)) => {
+                            crate::components::session_browser::handle_component(
+                                &ctx,
+                                &event,
+                                &event.guild_id.unwrap_or_default(),
+                                id,
+                                &event.data.values,
+                            )
+                            .await
+                        }
+                        Some(("history", id)) => {
+                            crate::components::history::handle_component(
+                                &ctx,
+                                &event,
+                                &event.channel_id,
+                                id,
+                                &event.data.values,
+                            )
+                            .await
+                        }
                         Some(("configurable_server", id)) => {
                             crate::components::configurable_server::handle_component(
                                 &ctx,
@@ -210,6 +321,30 @@ impl EventHandler for Handler {
                 .instrument(span)
                 .await;
             }
+            Interaction::Autocomplete(autocomplete) => {
+                let span = span!(
+                    Level::DEBUG,
+                    "autocomplete",
+                    interaction_id = autocomplete.id.0,
+                    guild_id = %autocomplete.guild_id.unwrap_or_default(),
+                    channel_id = %autocomplete.channel_id,
+                    user = %autocomplete.user,
+                    command_name = %autocomplete.data.name
+                );
+                async move {
+                    trace!(autocomplete = ?autocomplete, "received autocomplete interaction");
+                    let res = match autocomplete.data.name.as_str() {
+                        "use" => commands::r#use::autocomplete(&autocomplete, ctx.clone()).await,
+                        _ => Ok(()),
+                    };
+
+                    if let Err(why) = res {
+                        warn!(error = %why, "Failed to process autocomplete interaction");
+                    }
+                }
+                .instrument(span)
+                .await;
+            }
             _ => {
                 warn!("unknown interaction received");
             }
@@ -0,0 +1,166 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use serenity::model::prelude::{ChannelId, UserId};
+use tracing::Instrument;
+
+use crate::DB;
+
+/// How many queries are kept per channel, older ones are forgotten.
+pub const MAX_ENTRIES: u64 = 200;
+/// Older queries of a channel are forgotten every this many queries recorded in it,
+/// so a channel can briefly hold a few more than [`MAX_ENTRIES`].
+const PRUNE_EVERY: u32 = 20;
+
+/// Queries recorded in each channel since its history was last pruned.
+static UNPRUNED: LazyLock<Mutex<HashMap<ChannelId, u32>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// A query run in a session, as a message, with `/query` or with Big Query.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    /// Id of the record, only set on entries read back from the history.
+    #[serde(default, skip_serializing)]
+    pub key: Option<String>,
+    pub channel_id: ChannelId,
+    pub author: UserId,
+    pub query: String,
+    /// Big Query variables, Discord parameters are left out as they are set again on every run.
+    #[serde(default)]
+    pub vars: Option<HashMap<String, serde_json::Value>>,
+    pub duration: Duration,
+    /// First error of the query, if any of its statements failed.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Unix timestamp in seconds.
+    pub ran: u64,
+}
+
+impl HistoryEntry {
+    pub fn new(
+        channel_id: ChannelId,
+        author: UserId,
+        query: String,
+        vars: Option<HashMap<String, serde_json::Value>>,
+        duration: Duration,
+        error: Option<String>,
+    ) -> Self {
+        HistoryEntry {
+            key: None,
+            channel_id,
+            author,
+            query,
+            vars,
+            duration,
+            error,
+            ran: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+
+    /// One line summary for the history browser.
+    pub fn describe(&self) -> String {
+        format!(
+            "{} <@{}> <t:{}:R>, took {}",
+            match self.error {
+                Some(_) => "❌",
+                None => "✅",
+            },
+            self.author,
+            self.ran,
+            self.took()
+        )
+    }
+
+    /// How long the query took, to the millisecond.
+    pub fn took(&self) -> String {
+        humantime::format_duration(Duration::from_millis(self.duration.as_millis() as u64))
+            .to_string()
+    }
+}
+
+/// Indexes the history by channel, as every read and prune only looks at one channel.
+pub async fn define() -> Result<(), surrealdb::Error> {
+    DB.query("DEFINE INDEX IF NOT EXISTS query_history_channel ON query_history FIELDS channel_id")
+        .await?
+        .check()?;
+    Ok(())
+}
+
+/// Records a query in the background, failing quietly as history is only a convenience.
+/// Keys are ULIDs, so they sort in the order queries ran.
+pub fn record(entry: HistoryEntry) {
+    let channel = entry.channel_id;
+    let prune = {
+        let mut unpruned = UNPRUNED.lock().unwrap();
+        let count = unpruned.entry(channel).or_default();
+        *count += 1;
+        match *count >= PRUNE_EVERY {
+            true => {
+                *count = 0;
+                true
+            }
+            false => false,
+        }
+    };
+    tokio::spawn(
+        async move {
+            let result = DB
+                .query("CREATE type::thing('query_history', rand::ulid()) CONTENT $entry RETURN NONE")
+                .bind(("entry", entry))
+                .await;
+            if let Err(err) = result.and_then(surrealdb::Response::check) {
+                warn!(error = %err, channel_id = %channel, "Failed to record query history");
+            }
+            if prune {
+                let result = DB
+                    .query("DELETE query_history WHERE channel_id = $channel AND id < (SELECT VALUE id FROM query_history WHERE channel_id = $channel ORDER BY id DESC LIMIT 1 START $max)[0]")
+                    .bind(("channel", channel))
+                    .bind(("max", MAX_ENTRIES))
+                    .await;
+                if let Err(err) = result.and_then(surrealdb::Response::check) {
+                    warn!(error = %err, channel_id = %channel, "Failed to prune query history");
+                }
+            }
+        }
+        .in_current_span(),
+    );
+}
+
+/// One page of the history of a channel, newest first, and how many entries there are in total.
+pub async fn page(
+    channel_id: ChannelId,
+    page: usize,
+    page_size: usize,
+) -> Result<(Vec<HistoryEntry>, u64), surrealdb::Error> {
+    let mut response = DB
+        .query("SELECT *, meta::id(id) AS key OMIT id FROM query_history WHERE channel_id = $channel ORDER BY key DESC LIMIT $limit START $start")
+        .query("SELECT count() FROM query_history WHERE channel_id = $channel GROUP ALL")
+        .bind(("channel", channel_id))
+        .bind(("limit", page_size))
+        .bind(("start", page * page_size))
+        .await?;
+    let entries: Vec<HistoryEntry> = response.take(0)?;
+    let total: Option<u64> = response.take((1, "count"))?;
+    Ok((entries, total.unwrap_or_default()))
+}
+
+/// An entry of the history of `channel_id`, entries of other channels can't be reached.
+pub async fn get(
+    channel_id: ChannelId,
+    key: &str,
+) -> Result<Option<HistoryEntry>, surrealdb::Error> {
+    let entry: Option<HistoryEntry> = DB
+        .query("SELECT *, meta::id(id) AS key OMIT id FROM type::thing('query_history', $key) WHERE channel_id = $channel")
+        .bind(("key", key.to_string()))
+        .bind(("channel", channel_id))
+        .await?
+        .take(0)?;
+    Ok(entry)
+}
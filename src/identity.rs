@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serenity::model::prelude::UserId;
+use surrealdb::{dbs::Session, iam, opt::auth::Root};
+
+use crate::session_db::SessionDb;
+
+/// The bot's own user on every session datastore, used for exports and as the default identity.
+pub const ROOT: Root<'static> = Root {
+    username: "root",
+    password: "root",
+};
+
+pub const IDENTITY_FIELD: &str = "Querying as";
+
+/// Who a user's queries are run as, when it isn't the default root user.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Identity {
+    /// Human readable description for the session panel.
+    pub label: String,
+    /// Token to switch back to this identity, `None` for an anonymous session.
+    pub token: Option<String>,
+}
+
+/// Describes who everyone in a session queries as, for the session panel.
+pub fn describe(identities: &HashMap<UserId, Identity>) -> String {
+    if identities.is_empty() {
+        return "Everyone queries as the root user, use `/auth` to change".to_string();
+    }
+    let mut lines = identities
+        .iter()
+        .map(|(user, identity)| format!("<@{user}>: {}", identity.label))
+        .collect::<Vec<_>>();
+    lines.sort();
+    lines.push("Everyone else: root user".to_string());
+    lines.join("\n")
+}
+
+/// Signs `identity` in again on `db`, for sessions restored after a restart.
+pub async fn session(db: &SessionDb, identity: &Identity) -> surrealdb::Result<Session> {
+    let mut session = Session::default();
+    if let Some(token) = &identity.token {
+        iam::verify::token(db.kvs(), &mut session, token).await?;
+    }
+    Ok(session)
+}
@@ -1,15 +1,37 @@
 pub mod channel_info;
+pub mod checkpoints;
 pub mod commands;
 pub mod components;
 pub mod config;
+pub mod dashboards;
 pub mod db_utils;
+pub mod diff;
 pub mod handler;
+pub mod history;
+pub mod identity;
+pub mod live;
+pub mod location;
+pub mod maintenance;
+pub mod memory;
+pub mod params;
+pub mod persistence;
 pub mod premade;
+pub mod quotas;
+pub mod schedules;
+pub mod session_db;
+pub mod session_info;
 pub mod stats;
 pub mod utils;
 
-use futures::StreamExt;
-use serde::Serialize;
+use checkpoints::Checkpoint;
+use config::Engine;
+use dashboards::Dashboard;
+use history::HistoryEntry;
+use identity::Identity;
+use location::Location;
+use quotas::{Exceeded, Measurements, Quotas, MEASURE_INTERVAL};
+use schedules::Schedule;
+use serde::{Deserialize, Serialize};
 use serde_json::ser::PrettyFormatter;
 use serenity::{
     builder::CreateEmbed,
@@ -17,22 +39,25 @@ use serenity::{
     model::{
         prelude::{
             application_command::ApplicationCommandInteraction, component::ButtonStyle::Primary,
-            Attachment, AttachmentType, ChannelId,
+            Attachment, AttachmentType, ChannelId, MessageId, UserId,
         },
         user::User,
     },
     prelude::Context,
 };
-use surrealdb::{opt::IntoQuery, sql, Error, Response};
-use tokio::sync::Mutex;
+use session_db::{Handle, Response, SessionDb, Writes};
+use surrealdb::{dbs::Session, sql, Error};
 use tokio::time::{Duration, Instant};
-use utils::{ephemeral_interaction_edit, CmdError, ToInteraction, MAX_FILE_SIZE};
+use tokio::{sync::Mutex, task::AbortHandle};
+use tracing::Instrument;
+use utils::{ephemeral_interaction_edit, text_attachment, CmdError, ToInteraction};
 
 #[macro_use]
 extern crate tracing;
 
-use std::collections::HashMap;
-use std::{cmp::Ordering, sync::LazyLock};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock};
 
 use surrealdb::engine::local::Db;
 use surrealdb::Surreal;
@@ -43,19 +68,67 @@ pub static DB: LazyLock<Surreal<Db>> = LazyLock::new(Surreal::init);
 
 pub const BIG_QUERY_SENT_KEY: &str = "Query sent";
 pub const BIG_QUERY_VARS_KEY: &str = "Variables sent";
+pub const MAX_VERSIONS: usize = 10;
 
 #[derive(Debug, Clone)]
 pub struct Conn {
-    db: Surreal<Db>,
+    db: SessionDb,
+    engine: Engine,
     last_used: Instant,
     conn_type: ConnType,
     ttl: Duration,
+    /// When the session was started, for the maximum lifetime.
+    created: Instant,
+    /// How many times the session was extended from an expiry warning or `/sessions`.
+    extensions: u32,
+    /// How long before expiring the channel is warned, zero for no warning.
+    ttl_warning: Duration,
+    /// Longest the session can live, zero for no limit.
+    max_lifetime: Duration,
+    /// How many queries users ran, sent as messages, with `/query` or with Big Query.
+    queries: Arc<AtomicU64>,
     pretty: bool,
     json: bool,
     require_query: bool,
+    /// Recent points in time that can be queried with `VERSION` in versioned sessions.
+    versions: Arc<Mutex<VecDeque<String>>>,
+    checkpoints: Vec<Checkpoint>,
+    /// Whether message queries that write data can be undone.
+    undo: bool,
+    undo_snapshot: Arc<Mutex<Option<UndoSnapshot>>>,
+    /// Running live queries, keyed by the message announcing them.
+    live_queries: Arc<Mutex<HashMap<MessageId, AbortHandle>>>,
+    /// Dashboards kept up to date by a background task, keyed by their message.
+    dashboards: Arc<Mutex<HashMap<MessageId, (Dashboard, AbortHandle)>>>,
+    /// Queries run in the background on an interval, keyed by their id.
+    schedules: Arc<Mutex<BTreeMap<u32, (Schedule, AbortHandle)>>>,
+    /// Parameters set with `/param`, bound on every query run in the session.
+    params: Arc<Mutex<BTreeMap<String, serde_json::Value>>>,
+    /// Namespace and database picked with `/use`.
+    location: Arc<Mutex<Location>>,
+    /// Limits from the server config at the time the session was created.
+    quotas: Quotas,
+    /// The quota the session went over, queries adding data are refused until it's back under.
+    over_quota: Arc<Mutex<Option<Exceeded>>>,
+    measurements: Arc<Mutex<Measurements>>,
+    /// Identities picked by users with `/auth`, everyone else queries as the root user.
+    identities: Arc<Mutex<HashMap<UserId, Identity>>>,
+    /// Signed in sessions of the users in `identities`, missing for identities that could not be restored.
+    sessions: Arc<Mutex<HashMap<UserId, Session>>>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// The state of a session's database right before a message query wrote to it.
+#[derive(Debug)]
+pub struct UndoSnapshot {
+    message_id: MessageId,
+    /// Who sent the query, the only one who can undo it.
+    author: UserId,
+    export: Vec<u8>,
+    /// Writes counted right after the query, the snapshot is stale once anything else writes.
+    writes: Writes,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ConnType {
     ConnectedChannel,
     EphemeralChannel,
@@ -63,12 +136,158 @@ pub enum ConnType {
 }
 
 impl Conn {
+    /// When the session expires unless it's used again.
+    pub fn expires_at(&self) -> Instant {
+        let idle = self.last_used + self.ttl;
+        match self.max_lifetime.is_zero() {
+            true => idle,
+            false => idle.min(self.created + self.max_lifetime),
+        }
+    }
+
+    /// Whether using or extending the session can't push its expiry back anymore.
+    pub fn at_max_lifetime(&self) -> bool {
+        !self.max_lifetime.is_zero()
+            && self.created + self.max_lifetime <= Instant::now() + self.ttl
+    }
+
+    /// Restarts the TTL, as far as the maximum lifetime allows.
+    pub fn extend(&mut self) {
+        self.last_used = Instant::now();
+        self.extensions += 1;
+    }
+
+    pub fn record_query(&self) {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn queries(&self) -> u64 {
+        self.queries.load(Ordering::Relaxed)
+    }
+
+    /// Remembers the current time as a point users can travel back to in versioned sessions.
+    pub async fn record_version(&self) {
+        if self.engine != Engine::SurrealKvVersioned {
+            return;
+        }
+        let mut versions = self.versions.lock().await;
+        if versions.len() >= MAX_VERSIONS {
+            versions.pop_front();
+        }
+        versions.push_back(sql::Datetime::default().to_string());
+    }
+
+    /// Writes counted on the session's datastore so far, see [`Conn::set_undo`].
+    pub fn writes(&self) -> Writes {
+        self.db.writes()
+    }
+
+    /// Keeps an export taken before the query in `message_id` ran, replacing any older snapshot.
+    /// `before` are the writes counted before the export, the snapshot is only kept
+    /// if nothing was writing then and the query is the only write since.
+    pub async fn set_undo(
+        &self,
+        message_id: MessageId,
+        author: UserId,
+        export: Vec<u8>,
+        before: Writes,
+    ) -> bool {
+        let writes = self.writes();
+        let mut snapshot = self.undo_snapshot.lock().await;
+        if !before.idle() || !writes.one_since(before) {
+            snapshot.take();
+            return false;
+        }
+        *snapshot = Some(UndoSnapshot {
+            message_id,
+            author,
+            export,
+            writes,
+        });
+        true
+    }
+
+    /// Takes the snapshot for `message_id`, only for its author and if nothing has written since.
+    pub async fn take_undo(
+        &self,
+        message_id: MessageId,
+        user: UserId,
+    ) -> Result<Vec<u8>, CmdError> {
+        let mut snapshot = self.undo_snapshot.lock().await;
+        match snapshot.as_ref() {
+            Some(s) if s.message_id == message_id && s.author != user => {
+                Err(CmdError::NotQueryAuthor)
+            }
+            Some(s) if s.message_id == message_id && s.writes == self.writes() => {
+                Ok(snapshot.take().expect("snapshot checked above").export)
+            }
+            _ => Err(CmdError::UndoUnavailable),
+        }
+    }
+
+    /// Drops the undo snapshot, as the database has changed in a way it can't account for.
+    pub async fn forget_undo(&self) {
+        self.undo_snapshot.lock().await.take();
+    }
+
+    /// Stops a live query, returns whether it was still running.
+    pub async fn kill_live_query(&self, id: MessageId) -> bool {
+        match self.live_queries.lock().await.remove(&id) {
+            Some(task) => {
+                task.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub async fn kill_live_queries(&self) {
+        for (_, task) in self.live_queries.lock().await.drain() {
+            task.abort();
+        }
+    }
+
+    /// Stops refreshing a dashboard, returns whether it existed.
+    pub async fn remove_dashboard(&self, id: MessageId) -> bool {
+        match self.dashboards.lock().await.remove(&id) {
+            Some((_, task)) => {
+                task.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub async fn stop_dashboards(&self) {
+        for (_, (_, task)) in self.dashboards.lock().await.drain() {
+            task.abort();
+        }
+    }
+
+    /// Stops a scheduled query, returns whether it existed.
+    pub async fn remove_schedule(&self, id: u32) -> bool {
+        match self.schedules.lock().await.remove(&id) {
+            Some((_, task)) => {
+                task.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub async fn stop_schedules(&self) {
+        for (_, (_, task)) in std::mem::take(&mut *self.schedules.lock().await) {
+            task.abort();
+        }
+    }
+
     pub async fn import_from_attachment(
         &self,
         http: impl AsRef<Http>,
         i: impl ToInteraction,
         attachment: &Attachment,
     ) -> Result<(), anyhow::Error> {
+        self.forget_undo().await;
         ephemeral_interaction_edit(
             &http,
             i.clone(),
@@ -77,15 +296,23 @@ impl Conn {
             None,
         )
         .await?;
+        if let Err(exceeded) = self.check_attachment(attachment) {
+            return CmdError::QuotaExceeded(exceeded).edit(http, i).await;
+        }
         match attachment.download().await {
             Ok(bytes) => {
                 ephemeral_interaction_edit(&http, i.clone(), "Downloaded, now importing...", "Your data is currently being loaded, soon you'll be able to query your dataset! \n_Please wait for a confirmation that the dataset is loaded!_", None).await?;
-                match self
-                    .db
-                    .query(String::from_utf8_lossy(&bytes).into_owned())
-                    .await
-                {
+                let export = String::from_utf8_lossy(&bytes).into_owned();
+                // Exports made by the bot remember the namespace and database the session was using.
+                let location = Location::from_export(&export);
+                match self.root().await.query(export).await {
                     Ok(_) => {
+                        if let Some(location) = location {
+                            self.use_location(location).await?;
+                        }
+                        if let Err(exceeded) = self.check_usage().await {
+                            return CmdError::QuotaExceeded(exceeded).edit(http, i).await;
+                        }
                         ephemeral_interaction_edit(http, i, "Imported successfully!", "Your data has been imported successfully!\nYou can now query your dataset.", Some(true)).await?;
                         Ok(())
                     }
@@ -104,14 +331,157 @@ impl Conn {
         }
     }
 
-    #[must_use]
-    pub async fn export_to_attachment(&self) -> Result<Option<AttachmentType>, anyhow::Error> {
-        let mut acc = Vec::new();
+    /// A handle for the bot's own work as the root user, in the session's namespace and database.
+    pub async fn root(&self) -> Handle {
+        self.db.root(&*self.location.lock().await)
+    }
+
+    /// A handle querying as the identity `user` picked with `/auth`, or as root for `None` and everyone else,
+    /// with the session's parameters bound.
+    /// Users whose identity can't be used anymore are refused rather than run as someone else.
+    pub async fn handle(&self, user: Option<UserId>) -> Result<Handle, CmdError> {
+        let session = match user {
+            Some(user) if self.identities.lock().await.contains_key(&user) => {
+                match self.sessions.lock().await.get(&user) {
+                    Some(session) if !session_db::expired(session) => Some(session.clone()),
+                    _ => return Err(CmdError::IdentityExpired),
+                }
+            }
+            _ => None,
+        };
+        let handle = match session {
+            // Root and guests follow `/use`, users signed in to a namespace or database stay there.
+            Some(session) => {
+                let location = self.location().await;
+                let namespace = session.ns.clone().unwrap_or(location.namespace);
+                let database = session.db.clone().unwrap_or(location.database);
+                self.db.handle(session).at(&namespace, &database)
+            }
+            None => self.root().await,
+        };
+        handle
+            .with_vars(self.params.lock().await.clone())
+            .map_err(CmdError::BadQuery)
+    }
+
+    /// Runs `user`'s queries as `identity` with the session it signed in with, or as root for `None`.
+    /// Returns everyone's identities, for the session panel.
+    pub async fn set_identity(
+        &self,
+        user: UserId,
+        identity: Option<(Identity, Session)>,
+    ) -> String {
+        let mut identities = self.identities.lock().await;
+        let mut sessions = self.sessions.lock().await;
+        match identity {
+            Some((identity, session)) => {
+                identities.insert(user, identity);
+                sessions.insert(user, session);
+            }
+            None => {
+                identities.remove(&user);
+                sessions.remove(&user);
+            }
+        }
+        identity::describe(&identities)
+    }
 
-        let mut export_stream = self.db.export(()).await?;
-        while let Some(v) = export_stream.next().await {
-            acc.extend(v?);
+    /// Switches the namespace and database of the session, creating them if needed.
+    pub async fn use_location(&self, location: Location) -> Result<(), surrealdb::Error> {
+        self.forget_undo().await;
+        location.define(&self.root().await).await?;
+        *self.location.lock().await = location;
+        Ok(())
+    }
+
+    pub async fn location(&self) -> Location {
+        self.location.lock().await.clone()
+    }
+
+    /// Checks a query against the statement quota,
+    /// and refuses queries adding data while the session is over quota.
+    pub async fn check_query(&self, query: &str) -> Result<(), Exceeded> {
+        self.quotas.check_statements(query)?;
+        match &*self.over_quota.lock().await {
+            Some(exceeded) if grows(query) => Err(exceeded.clone()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_attachment(&self, attachment: &Attachment) -> Result<(), Exceeded> {
+        self.quotas.check_attachment(attachment.size)
+    }
+
+    /// Measures the session after something may have added data to it.
+    /// Sessions measured less than [`MEASURE_INTERVAL`] ago are measured again once it's over,
+    /// and until then the last measurement stands.
+    ///
+    /// Limits are checked after the fact, so a single statement like a `FOR` loop
+    /// can take a session past them before it's refused, only the query timeout bounds it.
+    pub async fn check_usage(&self) -> Result<(), Exceeded> {
+        let wait = {
+            let mut measurements = self.measurements.lock().await;
+            let wait = measurements.last.map_or(Duration::ZERO, |last| {
+                MEASURE_INTERVAL.saturating_sub(last.elapsed())
+            });
+            if wait.is_zero() {
+                measurements.last = Some(Instant::now());
+            } else if !measurements.pending {
+                measurements.pending = true;
+                let conn = self.clone();
+                tokio::spawn(
+                    async move {
+                        tokio::time::sleep(wait).await;
+                        {
+                            let mut measurements = conn.measurements.lock().await;
+                            measurements.pending = false;
+                            measurements.last = Some(Instant::now());
+                        }
+                        conn.remeasure().await.ok();
+                    }
+                    .in_current_span(),
+                );
+            }
+            wait
+        };
+        if wait.is_zero() {
+            return self.remeasure().await;
+        }
+        self.over_quota.lock().await.clone().map_or(Ok(()), Err)
+    }
+
+    async fn remeasure(&self) -> Result<(), Exceeded> {
+        let exceeded = match self.measure().await {
+            Ok(usage) => usage.err(),
+            Err(err) => {
+                warn!(error = %err, "Failed to measure session usage");
+                return Ok(());
+            }
+        };
+        *self.over_quota.lock().await = exceeded.clone();
+        exceeded.map_or(Ok(()), Err)
+    }
+
+    async fn measure(&self) -> Result<Result<(), Exceeded>, anyhow::Error> {
+        if self.quotas.max_records == 0 && self.quotas.max_export_size == 0 {
+            return Ok(Ok(()));
         }
+        let usage = quotas::measure(&self.root().await).await?;
+        Ok(self
+            .quotas
+            .check_records(usage.records)
+            .and_then(|_| self.quotas.check_export(usage.size)))
+    }
+
+    /// Exports every namespace and database of the session as SurrealQL.
+    pub async fn export(&self) -> Result<Vec<u8>, anyhow::Error> {
+        let location = self.location().await;
+        location::export_all(&self.root().await, &location).await
+    }
+
+    #[must_use]
+    pub async fn export_to_attachment(&self) -> Result<Option<AttachmentType>, anyhow::Error> {
+        let acc = self.export().await?;
 
         let reply_attachment = AttachmentType::Bytes {
             data: std::borrow::Cow::Owned(acc),
@@ -120,15 +490,37 @@ impl Conn {
         Ok(Some(reply_attachment))
     }
 
+    /// Replaces the contents of the session's databases with a SurrealQL export.
+    pub async fn restore(&self, export: &[u8]) -> Result<(), anyhow::Error> {
+        self.forget_undo().await;
+        let root = self.root().await;
+        location::remove_all(&root).await?;
+        root.query(String::from_utf8_lossy(export)).await?.check()?;
+        Ok(())
+    }
+
     pub async fn query(
         &self,
         ctx: &Context,
         channel: &ChannelId,
         interaction: Option<&ApplicationCommandInteraction>,
         user: &User,
-        query: impl IntoQuery + std::fmt::Display,
+        query: impl std::fmt::Display,
         vars: Option<HashMap<String, serde_json::Value>>,
     ) -> Result<(), anyhow::Error> {
+        let checked = match self.check_query(&query.to_string()).await {
+            Ok(()) => self.handle(Some(user.id)).await,
+            Err(exceeded) => Err(CmdError::QuotaExceeded(exceeded)),
+        };
+        let handle = match checked {
+            Ok(handle) => handle,
+            Err(err) => {
+                return match interaction {
+                    Some(i) => err.reply(ctx, i).await,
+                    None => err.send(ctx, channel).await,
+                }
+            }
+        };
         let query_message = match interaction {
             Some(i) => {
                 i.create_interaction_response(ctx, |r| {
@@ -219,17 +611,30 @@ impl Conn {
                     .await?
             }
         };
-        let mut query = self.db.query(query);
-        if let Some(vars) = vars {
-            query = query.bind(vars);
-        }
+        let query_text = query.to_string();
+        let big_query_vars = vars.clone();
+        // Discord parameters come last, so Big Query variables can't impersonate someone else.
+        let mut vars = vars.unwrap_or_default();
+        vars.extend(discord_vars(user, *channel, query_message.id));
+        self.forget_undo().await;
         let now = std::time::Instant::now();
-        let result = query.await;
+        let mut result = handle.query_with(&query_text, vars).await;
         let elapsed = now.elapsed();
-        let reply = match process(self.pretty, self.json, result) {
-            Ok(r) => r,
-            Err(e) => e.to_string(),
+        self.record_version().await;
+        self.record_query();
+        let streams = match result.as_mut() {
+            Ok(response) => live::take_streams(&query_text, response),
+            Err(_) => Vec::new(),
         };
+        let (reply, error) = process_outcome(self.pretty, self.json, result);
+        history::record(HistoryEntry::new(
+            *channel,
+            user.id,
+            query_text.clone(),
+            big_query_vars,
+            elapsed,
+            error,
+        ));
 
         if reply.len() < 4000 {
             channel
@@ -251,18 +656,10 @@ impl Conn {
                 })
                 .await?;
         } else {
-            let mut truncated = false;
-            let data = match reply.as_bytes().len().cmp(&MAX_FILE_SIZE) {
-                Ordering::Equal | Ordering::Less => reply.as_bytes(),
-                Ordering::Greater => {
-                    truncated = true;
-                    reply.as_bytes().split_at(MAX_FILE_SIZE).0
-                }
-            };
-            let reply_attachment = AttachmentType::Bytes {
-                data: std::borrow::Cow::Borrowed(data),
-                filename: format!("response.{}", if self.json { "json" } else { "sql" }),
-            };
+            let (reply_attachment, truncated) = text_attachment(
+                &reply,
+                format!("response.{}", if self.json { "json" } else { "sql" }),
+            );
             channel
                 .send_message(&ctx, |m| {
                     m
@@ -281,16 +678,28 @@ impl Conn {
                 .await
                 .unwrap();
         }
-        Ok(())
+        if has_writes(&query_text) {
+            if let Err(exceeded) = self.check_usage().await {
+                CmdError::QuotaExceeded(exceeded).send(ctx, channel).await?;
+            }
+        }
+        live::watch(ctx.clone(), *channel, self, streams).await
     }
 }
 
-/// Exports all DBCONNS to their respective channels and returns.
+/// Saves all DBCONNS to disk so they can be restored on the next startup.
+/// Sessions that fail to save are exported to their respective channels instead.
 /// Used as part of graceful shutdown.
 pub async fn shutdown(http: impl AsRef<Http>) -> Result<(), anyhow::Error> {
     let mut errors = vec![];
     for (c, conn) in DBCONNS.lock().await.iter() {
         let channel = ChannelId::from(*c);
+        match persistence::save(channel, conn).await {
+            Ok(()) => continue,
+            Err(err) => {
+                error!(error = %err, channel_id = %channel, "Failed to save session, exporting to channel instead");
+            }
+        }
         match conn.export_to_attachment().await {
             Ok(Some(attchment)) => {
                 let res = channel.send_message(&http, |m| {
@@ -318,10 +727,106 @@ pub async fn shutdown(http: impl AsRef<Http>) -> Result<(), anyhow::Error> {
     }
 }
 
+/// Parameters telling a query who sent it and from where, bound on every query users send.
+/// Ids are strings, as they don't fit in the integers some clients use.
+pub fn discord_vars(
+    user: &User,
+    channel: ChannelId,
+    message: MessageId,
+) -> HashMap<String, serde_json::Value> {
+    HashMap::from([
+        ("discord_user".to_string(), user.id.to_string().into()),
+        ("discord_user_name".to_string(), user.name.clone().into()),
+        ("channel_id".to_string(), channel.to_string().into()),
+        ("message_id".to_string(), message.to_string().into()),
+    ])
+}
+
+/// Keywords of the statements that write, as a rendered statement spells them.
+const WRITE_KEYWORDS: [&str; 10] = [
+    "CREATE", "UPDATE", "UPSERT", "DELETE", "RELATE", "INSERT", "DEFINE", "REMOVE", "ALTER",
+    "REBUILD",
+];
+
+/// Whether a statement holds something that may write when the datastore runs it,
+/// like a `(CREATE ...)` subquery or a call to a custom or scripting function.
+/// Looks at the rendered statement, past its own keyword, so strings that happen to hold these words count too.
+fn nested_writes(statement: &sql::Statement) -> bool {
+    let rendered = statement.to_string();
+    rendered.contains("fn::")
+        || rendered.contains("function(")
+        || rendered
+            .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .skip(1)
+            .any(|word| WRITE_KEYWORDS.contains(&word))
+}
+
+fn read_only(statement: &sql::Statement) -> bool {
+    matches!(
+        statement,
+        sql::Statement::Select(_)
+            | sql::Statement::Info(_)
+            | sql::Statement::Show(_)
+            | sql::Statement::Live(_)
+            | sql::Statement::Kill(_)
+            | sql::Statement::Use(_)
+            | sql::Statement::Option(_)
+            | sql::Statement::Sleep(_)
+            | sql::Statement::Begin(_)
+            | sql::Statement::Commit(_)
+            | sql::Statement::Cancel(_)
+    ) && !nested_writes(statement)
+}
+
+/// Whether a query may change the database, anything not known to be read-only counts as a write.
+pub fn has_writes(query: &str) -> bool {
+    match sql::parse(query) {
+        Ok(query) => query.iter().any(|statement| !read_only(statement)),
+        // Queries that don't parse fail before touching the database.
+        Err(_) => false,
+    }
+}
+
+/// Whether a query may add data, rather than only read or remove it.
+/// Removals count too when something nested in them may write, as that may add data of its own.
+pub fn grows(query: &str) -> bool {
+    match sql::parse(query) {
+        Ok(query) => query.iter().any(|statement| match statement {
+            sql::Statement::Delete(_) | sql::Statement::Remove(_) => nested_writes(statement),
+            _ => !read_only(statement),
+        }),
+        Err(_) => false,
+    }
+}
+
 pub fn process(
     pretty: bool,
     json: bool,
     res: surrealdb::Result<Response>,
+) -> Result<String, Error> {
+    render(pretty, json, res, &mut None)
+}
+
+/// Like [`process`], with errors rendered as the reply,
+/// also returning the first error of the query for its history.
+pub fn process_outcome(
+    pretty: bool,
+    json: bool,
+    res: surrealdb::Result<Response>,
+) -> (String, Option<String>) {
+    let mut error = None;
+    match render(pretty, json, res, &mut error) {
+        Ok(reply) => (reply, error),
+        Err(e) => (e.to_string(), Some(e.to_string())),
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn render(
+    pretty: bool,
+    json: bool,
+    res: surrealdb::Result<Response>,
+    error: &mut Option<String>,
 ) -> Result<String, Error> {
     // Check query response for an error
     let mut response = res?;
@@ -331,14 +836,18 @@ pub fn process(
     let value = if num_statements > 1 {
         let mut output = Vec::<sql::Value>::with_capacity(num_statements);
         for index in 0..num_statements {
-            output.push(match response.take::<surrealdb::Value>(index) {
-                Ok(v) => v.into_inner(),
-                Err(e) => sql::Value::from(e.to_string()),
+            output.push(match response.take(index) {
+                Ok(v) => v,
+                Err(e) => {
+                    let e = e.to_string();
+                    error.get_or_insert_with(|| e.clone());
+                    sql::Value::from(e)
+                }
             });
         }
         sql::Value::from(output)
     } else {
-        response.take::<surrealdb::Value>(0)?.into_inner()
+        response.take(0)?
     };
     // Check if we should emit JSON and/or prettify
     Ok(match (json, pretty) {
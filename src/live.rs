@@ -0,0 +1,167 @@
+use futures::StreamExt;
+use serenity::{
+    builder::CreateComponents,
+    http::Http,
+    model::prelude::{component::ButtonStyle::Danger, ChannelId, MessageId},
+};
+use surrealdb::{
+    dbs::{Action, Notification},
+    sql,
+};
+use tokio::time::{timeout_at, Duration, Instant};
+use tracing::Instrument;
+
+use crate::{
+    session_db::{LiveStream, Response},
+    Conn,
+};
+
+pub const MAX_LIVE_QUERIES: usize = 5;
+/// Notifications are collected for this long before being posted, to stay clear of rate limits.
+const BATCH_INTERVAL: Duration = Duration::from_secs(2);
+const MAX_BATCH_LENGTH: usize = 1800;
+
+/// Result indexes of the `LIVE SELECT` statements in a query, numbered the way the datastore does.
+fn live_indexes(query: &str) -> Vec<usize> {
+    let Ok(query) = sql::parse(query) else {
+        return Vec::new();
+    };
+    query
+        .iter()
+        // BEGIN, COMMIT and CANCEL don't produce a result.
+        .filter(|s| {
+            !matches!(
+                s,
+                sql::Statement::Begin(_) | sql::Statement::Commit(_) | sql::Statement::Cancel(_)
+            )
+        })
+        .enumerate()
+        .filter(|(_, s)| matches!(s, sql::Statement::Live(_)))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Takes the notification streams of the live queries in `response`.
+/// This has to happen before the response is dropped, as dropping a stream kills its live query.
+pub fn take_streams(query: &str, response: &mut Response) -> Vec<LiveStream> {
+    live_indexes(query)
+        .into_iter()
+        .filter_map(|i| match response.stream(i) {
+            Ok(stream) => Some(stream),
+            Err(err) => {
+                debug!(error = %err, index = i, "Live query did not start");
+                None
+            }
+        })
+        .collect()
+}
+
+fn kill_button(components: &mut CreateComponents, id: MessageId) -> &mut CreateComponents {
+    components.create_action_row(|r| {
+        r.create_button(|b| {
+            b.custom_id(format!("configurable_session:kill_live:{id}"))
+                .label("Kill live query")
+                .style(Danger)
+                .emoji('🛑')
+        })
+    })
+}
+
+fn describe(notification: Notification) -> String {
+    let action = match notification.action {
+        Action::Create => "CREATE",
+        Action::Update => "UPDATE",
+        Action::Delete => "DELETE",
+        _ => "UNKNOWN",
+    };
+    format!("{action} {}", notification.result)
+}
+
+async fn too_many(http: impl AsRef<Http>, channel: ChannelId) -> Result<(), anyhow::Error> {
+    channel
+        .say(
+            http,
+            format!(":warning: Sessions can have at most {MAX_LIVE_QUERIES} live queries, kill one before starting another."),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Posts the notifications of each stream into the channel until the live query is killed.
+pub async fn watch(
+    http: impl AsRef<Http> + Clone + Send + Sync + 'static,
+    channel: ChannelId,
+    conn: &Conn,
+    streams: Vec<LiveStream>,
+) -> Result<(), anyhow::Error> {
+    for mut stream in streams {
+        if conn.live_queries.lock().await.len() >= MAX_LIVE_QUERIES {
+            too_many(&http, channel).await?;
+            continue;
+        }
+
+        let id = channel
+            .send_message(&http, |m| {
+                m.content(":satellite: Live query started, changes will be posted here.")
+            })
+            .await?
+            .id;
+        channel
+            .edit_message(&http, id, |m| m.components(|c| kill_button(c, id)))
+            .await?;
+
+        let mut live_queries = conn.live_queries.lock().await;
+        // Other live queries may have started while the message was being sent.
+        if live_queries.len() >= MAX_LIVE_QUERIES {
+            drop(live_queries);
+            channel.delete_message(&http, id).await.ok();
+            too_many(&http, channel).await?;
+            continue;
+        }
+
+        let (http, registry) = (http.clone(), conn.live_queries.clone());
+        let task = tokio::spawn(
+            async move {
+                while let Some(first) = stream.next().await {
+                    let mut batch = vec![describe(first)];
+                    let deadline = Instant::now() + BATCH_INTERVAL;
+                    while let Ok(Some(next)) = timeout_at(deadline, stream.next()).await {
+                        batch.push(describe(next));
+                    }
+
+                    let mut content = String::new();
+                    let mut shown = 0;
+                    for line in &batch {
+                        if content.len() + line.len() > MAX_BATCH_LENGTH {
+                            break;
+                        }
+                        content.push_str(line);
+                        content.push('\n');
+                        shown += 1;
+                    }
+                    let hidden = match batch.len() - shown {
+                        0 => String::new(),
+                        n => format!("\n_...and {n} more changes_"),
+                    };
+                    if let Err(err) = channel
+                        .send_message(&http, |m| {
+                            m.content(format!("```sql\n{content}```{hidden}"))
+                                .components(|c| kill_button(c, id))
+                        })
+                        .await
+                    {
+                        warn!(error = %err, "Failed to post live query notifications");
+                    }
+                }
+                registry.lock().await.remove(&id);
+                channel
+                    .say(&http, ":satellite: Live query ended.")
+                    .await
+                    .ok();
+            }
+            .in_current_span(),
+        );
+        live_queries.insert(id, task.abort_handle());
+    }
+    Ok(())
+}
@@ -0,0 +1,232 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use surrealdb::sql;
+
+use crate::session_db::Handle;
+
+pub const LOCATION_FIELD: &str = "Namespace and database";
+
+/// Namespace and database a session's queries run in,
+/// unless the identity running them is tied to other ones.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Location {
+    pub namespace: String,
+    pub database: String,
+}
+
+impl Default for Location {
+    fn default() -> Self {
+        Location {
+            namespace: "test".to_string(),
+            database: "test".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` / `{}`", self.namespace, self.database)
+    }
+}
+
+impl Location {
+    /// Defines the namespace and database if they don't exist yet, so they show up in `INFO`.
+    pub async fn define(&self, db: &Handle) -> surrealdb::Result<()> {
+        db.query(format!(
+            "DEFINE NAMESPACE IF NOT EXISTS {ns}; USE NS {ns}; DEFINE DATABASE IF NOT EXISTS {db};",
+            ns = sql::Ident::from(self.namespace.as_str()),
+            db = sql::Ident::from(self.database.as_str()),
+        ))
+        .await?
+        .check()?;
+        Ok(())
+    }
+
+    fn use_statement(&self) -> String {
+        format!(
+            "USE NS {} DB {};",
+            sql::Ident::from(self.namespace.as_str()),
+            sql::Ident::from(self.database.as_str())
+        )
+    }
+
+    /// Finds where a session was when it was exported with `export_all`.
+    pub fn from_export(export: &str) -> Option<Self> {
+        let line = export.lines().rev().find(|l| l.starts_with("USE NS "))?;
+        let query = sql::parse(line).ok()?;
+        match query.first()? {
+            sql::Statement::Use(statement) => Some(Location {
+                namespace: statement.ns.clone()?,
+                database: statement.db.clone()?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Names of the keys of one kind of definition in the result of an `INFO` statement.
+async fn names(
+    db: &Handle,
+    query: String,
+    index: usize,
+    kind: &str,
+) -> surrealdb::Result<Vec<String>> {
+    let info = db.query(query).await?.take(index)?;
+    Ok(match info {
+        sql::Value::Object(mut info) => match info.remove(kind) {
+            Some(sql::Value::Object(names)) => names.0.into_keys().collect(),
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    })
+}
+
+pub async fn namespaces(db: &Handle) -> surrealdb::Result<Vec<String>> {
+    names(db, "INFO FOR ROOT".to_string(), 0, "namespaces").await
+}
+
+pub async fn databases(db: &Handle, namespace: &str) -> surrealdb::Result<Vec<String>> {
+    names(
+        db,
+        format!("USE NS {}; INFO FOR NS", sql::Ident::from(namespace)),
+        1,
+        "databases",
+    )
+    .await
+}
+
+pub async fn tables(
+    db: &Handle,
+    namespace: &str,
+    database: &str,
+) -> surrealdb::Result<Vec<String>> {
+    definitions(db, namespace, database, "tables").await
+}
+
+/// Names of one kind of definition of a database, like `functions` or `params`.
+pub async fn definitions(
+    db: &Handle,
+    namespace: &str,
+    database: &str,
+    kind: &str,
+) -> surrealdb::Result<Vec<String>> {
+    names(
+        db,
+        format!(
+            "USE NS {} DB {}; INFO FOR DB",
+            sql::Ident::from(namespace),
+            sql::Ident::from(database)
+        ),
+        1,
+        kind,
+    )
+    .await
+}
+
+pub async fn indexes(
+    db: &Handle,
+    namespace: &str,
+    database: &str,
+    table: &str,
+) -> surrealdb::Result<Vec<String>> {
+    names(
+        db,
+        format!(
+            "USE NS {} DB {}; INFO FOR TABLE {}",
+            sql::Ident::from(namespace),
+            sql::Ident::from(database),
+            sql::Ident::from(table)
+        ),
+        1,
+        "indexes",
+    )
+    .await
+}
+
+/// How many records a table holds.
+pub async fn count(
+    db: &Handle,
+    namespace: &str,
+    database: &str,
+    table: &str,
+) -> surrealdb::Result<u64> {
+    let records = db
+        .query(format!(
+            "USE NS {} DB {}; (SELECT count() FROM {} GROUP ALL)[0].count",
+            sql::Ident::from(namespace),
+            sql::Ident::from(database),
+            sql::Ident::from(table)
+        ))
+        .await?
+        .take(1)?;
+    Ok(match records {
+        sql::Value::Number(n) => n.as_int() as u64,
+        _ => 0,
+    })
+}
+
+/// Exports every database of every namespace, along with the users and access methods
+/// of the root and the namespaces, ending with a `USE` statement for `current`.
+pub async fn export_all(db: &Handle, current: &Location) -> Result<Vec<u8>, anyhow::Error> {
+    let mut acc = Vec::new();
+    let root = db.auth_definitions(None).await?;
+    if !root.is_empty() {
+        acc.extend(
+            format!(
+                "\n-- ------------------------------\n-- ROOT\n-- ------------------------------\n\n{}\n",
+                root.join("\n")
+            )
+            .into_bytes(),
+        );
+    }
+    for namespace in namespaces(db).await? {
+        acc.extend(
+            format!(
+                "\n-- ------------------------------\n-- NAMESPACE\n-- ------------------------------\n\nDEFINE NAMESPACE IF NOT EXISTS {ns};\nUSE NS {ns};\n{}\n",
+                db.auth_definitions(Some(&namespace)).await?.join("\n"),
+                ns = sql::Ident::from(namespace.as_str()),
+            )
+            .into_bytes(),
+        );
+        for database in databases(db, &namespace).await? {
+            let location = Location {
+                namespace: namespace.clone(),
+                database,
+            };
+            acc.extend(
+                format!(
+                    "\n-- ------------------------------\n-- LOCATION\n-- ------------------------------\n\nDEFINE NAMESPACE IF NOT EXISTS {ns};\nUSE NS {ns};\nDEFINE DATABASE IF NOT EXISTS {db};\n{use_statement}\n",
+                    ns = sql::Ident::from(location.namespace.as_str()),
+                    db = sql::Ident::from(location.database.as_str()),
+                    use_statement = location.use_statement(),
+                )
+                .into_bytes(),
+            );
+            acc.extend(
+                db.clone()
+                    .at(&location.namespace, &location.database)
+                    .export()
+                    .await?,
+            );
+        }
+    }
+    acc.extend(format!("\n{}\n", current.use_statement()).into_bytes());
+    Ok(acc)
+}
+
+/// Removes every database, keeping namespaces and what's defined on them.
+pub async fn remove_all(db: &Handle) -> surrealdb::Result<()> {
+    for namespace in namespaces(db).await? {
+        for database in databases(db, &namespace).await? {
+            db.query(format!(
+                "USE NS {}; REMOVE DATABASE IF EXISTS {}",
+                sql::Ident::from(namespace.as_str()),
+                sql::Ident::from(database.as_str())
+            ))
+            .await?
+            .check()?;
+        }
+    }
+    Ok(())
+}
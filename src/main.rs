@@ -10,7 +10,7 @@ use tracing::{error, info};
 use surrealdb::engine::local::{Mem, RocksDb};
 
 use surreal_bot::handler::Handler;
-use surreal_bot::{stats, DB};
+use surreal_bot::{history, memory, persistence, stats, DB};
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
@@ -45,6 +45,7 @@ async fn main() -> Result<(), anyhow::Error> {
         }
     }
     DB.use_ns("SurrealBot").use_db("SurrealBot").await?;
+    history::define().await?;
 
     let token = env::var("DISCORD_TOKEN")?;
 
@@ -57,6 +58,8 @@ async fn main() -> Result<(), anyhow::Error> {
     let shard_manager = client.shard_manager.clone();
     let http = client.cache_and_http.http.clone();
     stats::start(http.clone());
+    memory::start(http.clone());
+    persistence::start();
     tokio::spawn(async move {
         tokio::signal::ctrl_c()
             .await
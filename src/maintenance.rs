@@ -0,0 +1,151 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serenity::{
+    http::Http,
+    model::prelude::{ChannelId, GuildChannel, GuildId, UserId},
+};
+use tokio::{sync::Mutex, task::AbortHandle, time::sleep};
+use tracing::Instrument;
+
+use crate::{
+    utils::{clean_channel, system_message},
+    DBCONNS,
+};
+
+/// How long before a maintenance window sessions are warned.
+pub const WARNINGS: [Duration; 4] = [
+    Duration::from_secs(60 * 60),
+    Duration::from_secs(15 * 60),
+    Duration::from_secs(5 * 60),
+    Duration::from_secs(60),
+];
+
+/// Maintenance has to be planned at least this far ahead, so sessions get the last warning.
+pub const MIN_NOTICE: Duration = Duration::from_secs(60);
+
+/// Which sessions a clean up or maintenance window applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    Guild(GuildId),
+    /// Every session of every server, only for the owners of the bot.
+    Global,
+}
+
+/// Scheduled maintenance windows, at most one per scope.
+static WINDOWS: LazyLock<Mutex<HashMap<Scope, (SystemTime, AbortHandle)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Whether `user` owns the bot application, or is on the team owning it.
+pub async fn is_owner(http: impl AsRef<Http>, user: UserId) -> Result<bool, anyhow::Error> {
+    let info = http.as_ref().get_current_application_info().await?;
+    Ok(info.owner.id == user
+        || info
+            .team
+            .is_some_and(|team| team.members.iter().any(|m| m.user.id == user)))
+}
+
+/// Channels holding a session within `scope`.
+pub async fn sessions(http: impl AsRef<Http>, scope: Scope) -> Vec<GuildChannel> {
+    let ids: Vec<u64> = DBCONNS.lock().await.keys().copied().collect();
+    let mut channels = Vec::new();
+    for id in ids {
+        match ChannelId(id)
+            .to_channel(http.as_ref())
+            .await
+            .map(|c| c.guild())
+        {
+            Ok(Some(channel))
+                if scope == Scope::Global || scope == Scope::Guild(channel.guild_id) =>
+            {
+                channels.push(channel)
+            }
+            Ok(_) => {}
+            Err(err) => warn!(error = %err, channel_id = id, "Failed to get session channel"),
+        }
+    }
+    channels
+}
+
+/// Exports and cleans every session within `scope`, returning how many there were.
+pub async fn clean_all(http: Arc<Http>, scope: Scope) -> usize {
+    let channels = sessions(&http, scope).await;
+    let count = channels.len();
+    for channel in channels {
+        let http = http.clone();
+        tokio::spawn(async move { clean_channel(channel, &http).await }.in_current_span());
+    }
+    count
+}
+
+pub fn timestamp(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Cleans every session within `scope` at `at`, warning them beforehand.
+/// Replaces any maintenance window already scheduled for the scope.
+pub async fn schedule(http: Arc<Http>, scope: Scope, at: SystemTime) {
+    let mut windows = WINDOWS.lock().await;
+    let task = tokio::spawn(
+        async move {
+            for warning in WARNINGS {
+                let Ok(until) = at.duration_since(SystemTime::now()) else {
+                    break;
+                };
+                if until < warning {
+                    continue;
+                }
+                sleep(until - warning).await;
+                warn_sessions(&http, scope, at).await;
+            }
+            if let Ok(until) = at.duration_since(SystemTime::now()) {
+                sleep(until).await;
+            }
+            info!(?scope, "Starting maintenance");
+            {
+                // The window may have been replaced while this one was about to start.
+                let mut windows = WINDOWS.lock().await;
+                if windows
+                    .get(&scope)
+                    .is_some_and(|(planned, _)| *planned == at)
+                {
+                    windows.remove(&scope);
+                }
+            }
+            clean_all(http, scope).await;
+        }
+        .in_current_span(),
+    );
+    if let Some((_, previous)) = windows.insert(scope, (at, task.abort_handle())) {
+        previous.abort();
+    }
+}
+
+/// Cancels the maintenance window of `scope`, returning when it would have happened.
+pub async fn cancel(scope: Scope) -> Option<SystemTime> {
+    let (at, task) = WINDOWS.lock().await.remove(&scope)?;
+    task.abort();
+    Some(at)
+}
+
+async fn warn_sessions(http: &Arc<Http>, scope: Scope, at: SystemTime) {
+    for channel in sessions(http, scope).await {
+        system_message(
+            http,
+            &channel.id,
+            "Maintenance scheduled",
+            format!(
+                "This session will be exported and cleaned <t:{at}:R>, at <t:{at}:t>, for maintenance.\nYou can use the Reconnect button on its export afterwards, or `/export` now to keep a copy.",
+                at = timestamp(at)
+            ),
+            None,
+            None,
+            None,
+        )
+        .await
+        .ok();
+    }
+}
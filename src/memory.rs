@@ -0,0 +1,153 @@
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, LazyLock},
+};
+
+use serenity::{http::Http, model::id::ChannelId};
+use tokio::{
+    sync::Mutex,
+    time::{interval, Duration, MissedTickBehavior},
+};
+
+use crate::{
+    persistence,
+    quotas::{self, format_size},
+    utils::{clean_channel, system_message},
+    Conn, DBCONNS,
+};
+
+const MB: u64 = 1024 * 1024;
+const DEFAULT_BUDGET_MB: u64 = 2048;
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Footprints of the sessions as last measured, by channel id.
+static FOOTPRINTS: LazyLock<Mutex<HashMap<u64, u64>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Total memory sessions may use before the least recently used ones are evicted.
+/// Can be overridden with the `MEMORY_BUDGET_MB` environment variable, 0 turns eviction off.
+pub fn budget() -> u64 {
+    env::var("MEMORY_BUDGET_MB")
+        .ok()
+        .and_then(|mb| mb.parse().ok())
+        .unwrap_or(DEFAULT_BUDGET_MB)
+        * MB
+}
+
+/// Rough estimate of the memory a session holds on to:
+/// its data for in-memory engines, estimated from a sample of each table, and its undo snapshot.
+/// On-disk engines keep their data out of memory, so only the snapshot counts for them.
+async fn footprint(conn: &Conn) -> Result<u64, anyhow::Error> {
+    let data = match conn.engine.is_on_disk() {
+        true => 0,
+        false => quotas::measure(&conn.root().await).await?.size,
+    };
+    let snapshot = conn
+        .undo_snapshot
+        .lock()
+        .await
+        .as_ref()
+        .map_or(0, |s| s.export.len() as u64);
+    Ok(data + snapshot)
+}
+
+/// The footprint of the session in `channel` as of the last check, if it has been measured yet.
+pub async fn last_footprint(channel: ChannelId) -> Option<u64> {
+    FOOTPRINTS.lock().await.get(channel.as_u64()).copied()
+}
+
+/// Periodically measures the footprint of all sessions, and checks it against the budget.
+pub fn start(http: Arc<Http>) {
+    let budget = budget();
+    if budget == 0 {
+        info!("Memory budget disabled, sessions won't be evicted");
+    }
+    tokio::spawn(async move {
+        let mut ticks = interval(CHECK_INTERVAL);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticks.tick().await;
+            supervise(&http, budget).await;
+        }
+    });
+}
+
+/// Evicts the least recently used sessions until the rest fit within `budget`, unless it's 0.
+async fn supervise(http: &Arc<Http>, budget: u64) {
+    let conns: Vec<(u64, Conn)> = DBCONNS
+        .lock()
+        .await
+        .iter()
+        .map(|(id, conn)| (*id, conn.clone()))
+        .collect();
+
+    let mut sessions = Vec::with_capacity(conns.len());
+    let mut total = 0;
+    for (id, conn) in conns {
+        match footprint(&conn).await {
+            Ok(size) => {
+                total += size;
+                sessions.push((id, conn.last_used, size));
+            }
+            Err(err) => warn!(error = %err, channel_id = id, "Failed to measure session"),
+        }
+    }
+    *FOOTPRINTS.lock().await = sessions.iter().map(|(id, _, size)| (*id, *size)).collect();
+    debug!(total = %format_size(total), budget = %format_size(budget), "Measured sessions");
+    if budget == 0 || total <= budget {
+        return;
+    }
+
+    sessions.sort_by_key(|(_, last_used, _)| *last_used);
+    for (id, _, size) in sessions {
+        if total <= budget {
+            break;
+        }
+        let channel = ChannelId(id);
+        // Sessions cleaned up since, or used in the meantime, are left alone.
+        let still_idle = DBCONNS
+            .lock()
+            .await
+            .get(&id)
+            .is_some_and(|c| c.last_used.elapsed() >= CHECK_INTERVAL);
+        if !still_idle {
+            continue;
+        }
+        info!(channel_id = id, size = %format_size(size), "Evicting session over memory budget");
+        system_message(
+            http,
+            &channel,
+            "Session evicted",
+            format!(
+                "The bot is running low on memory, and this session was the least recently used one ({}).\nIts export is attached below, use the Reconnect button to pick up where you left off.",
+                format_size(size)
+            ),
+            None,
+            None,
+            None,
+        )
+        .await
+        .ok();
+        match channel.to_channel(http).await.map(|c| c.guild()) {
+            Ok(Some(guild_channel)) => clean_channel(guild_channel, http).await,
+            Ok(None) => drop_session(id).await,
+            Err(err) => {
+                warn!(error = %err, channel_id = id, "Failed to get channel to evict, dropping session");
+                drop_session(id).await;
+            }
+        }
+        total = total.saturating_sub(size);
+    }
+}
+
+/// Drops a session whose channel can't be cleaned up, stopping everything it runs in the background.
+async fn drop_session(id: u64) {
+    let Some(conn) = DBCONNS.lock().await.remove(&id) else {
+        return;
+    };
+    conn.kill_live_queries().await;
+    conn.stop_dashboards().await;
+    conn.stop_schedules().await;
+    persistence::remove(ChannelId(id)).await;
+}
@@ -0,0 +1,50 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+pub const MAX_PARAMS: usize = 10;
+/// Longest value shown on the session panel, longer ones are cut short.
+const MAX_DISPLAY_LENGTH: usize = 60;
+/// Bound on every query by `discord_vars`, which would hide session parameters with these names.
+const RESERVED: [&str; 4] = [
+    "discord_user",
+    "discord_user_name",
+    "channel_id",
+    "message_id",
+];
+
+/// Strips the `$` users may type in front of a parameter name.
+pub fn normalize_name(name: &str) -> &str {
+    name.strip_prefix('$').unwrap_or(name)
+}
+
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !RESERVED.contains(&name)
+}
+
+/// Renders the parameters of a session for the session panel.
+pub fn describe(params: &BTreeMap<String, Value>) -> Option<String> {
+    if params.is_empty() {
+        return None;
+    }
+    Some(
+        params
+            .iter()
+            .map(|(name, value)| {
+                let mut value = value.to_string();
+                if value.len() > MAX_DISPLAY_LENGTH {
+                    let end = (0..=MAX_DISPLAY_LENGTH)
+                        .rev()
+                        .find(|i| value.is_char_boundary(*i))
+                        .unwrap_or(0);
+                    value.truncate(end);
+                    value.push('…');
+                }
+                format!("`${name}` = `{value}`")
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
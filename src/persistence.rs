@@ -0,0 +1,352 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    http::{error::ErrorResponse, HttpError, StatusCode},
+    model::prelude::{ChannelId, UserId},
+    prelude::Context,
+};
+use tokio::{
+    sync::Mutex,
+    time::{interval, Duration, Instant, MissedTickBehavior},
+};
+use tracing::Instrument;
+
+use crate::{
+    checkpoints::Checkpoint,
+    config::Engine,
+    dashboards::{self, Dashboard},
+    db_utils::get_config,
+    identity::{self, Identity},
+    location::Location,
+    schedules::{self, Schedule},
+    utils::{open_db_instance, register_conn},
+    Conn, ConnType, DBCONNS,
+};
+
+/// How often running sessions are saved, so a crash loses at most this much of them.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+static RESTORED: AtomicBool = AtomicBool::new(false);
+
+/// Directory where sessions are checkpointed between bot restarts.
+/// Can be overridden with the `SESSIONS_PATH` environment variable.
+pub fn sessions_dir() -> PathBuf {
+    PathBuf::from(env::var("SESSIONS_PATH").unwrap_or_else(|_| "sessions".to_string()))
+}
+
+/// Directory holding the data of a channel's on-disk database instance.
+pub fn engine_dir(channel_id: ChannelId) -> PathBuf {
+    sessions_dir().join("engines").join(channel_id.to_string())
+}
+
+fn saved_dir() -> PathBuf {
+    sessions_dir().join("saved")
+}
+
+/// Everything needed to bring a `Conn` back to life, minus the database contents,
+/// which are stored next to it as a `.surql` export for in-memory sessions,
+/// or simply left in place for on-disk ones.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SavedSession {
+    pub channel_id: ChannelId,
+    #[serde(default)]
+    pub engine: Engine,
+    pub conn_type: ConnType,
+    pub ttl: Duration,
+    /// How long the session had been idle when it was saved.
+    /// Downtime is not counted towards the TTL.
+    pub idle: Duration,
+    pub pretty: bool,
+    pub json: bool,
+    pub require_query: bool,
+    #[serde(default)]
+    pub versions: Vec<String>,
+    #[serde(default)]
+    pub checkpoints: Vec<Checkpoint>,
+    #[serde(default)]
+    pub undo: bool,
+    #[serde(default)]
+    pub dashboards: Vec<Dashboard>,
+    #[serde(default)]
+    pub schedules: Vec<Schedule>,
+    #[serde(default)]
+    pub location: Location,
+    #[serde(default)]
+    pub params: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub identities: HashMap<UserId, Identity>,
+    /// How long the session had been running when it was saved, for its maximum lifetime.
+    #[serde(default)]
+    pub age: Duration,
+    #[serde(default)]
+    pub extensions: u32,
+    #[serde(default)]
+    pub queries: u64,
+}
+
+impl SavedSession {
+    async fn from_conn(channel_id: ChannelId, conn: &Conn) -> Self {
+        SavedSession {
+            channel_id,
+            engine: conn.engine,
+            conn_type: conn.conn_type.clone(),
+            ttl: conn.ttl,
+            idle: conn.last_used.elapsed(),
+            pretty: conn.pretty,
+            json: conn.json,
+            require_query: conn.require_query,
+            versions: conn.versions.lock().await.iter().cloned().collect(),
+            checkpoints: conn.checkpoints.clone(),
+            undo: conn.undo,
+            dashboards: conn
+                .dashboards
+                .lock()
+                .await
+                .values()
+                .map(|(dashboard, _)| dashboard.clone())
+                .collect(),
+            schedules: conn
+                .schedules
+                .lock()
+                .await
+                .values()
+                .map(|(schedule, _)| schedule.clone())
+                .collect(),
+            location: conn.location().await,
+            params: conn.params.lock().await.clone(),
+            identities: conn.identities.lock().await.clone(),
+            age: conn.created.elapsed(),
+            extensions: conn.extensions,
+            queries: conn.queries(),
+        }
+    }
+
+    fn meta_path(channel_id: ChannelId) -> PathBuf {
+        saved_dir().join(format!("{channel_id}.json"))
+    }
+
+    fn export_path(channel_id: ChannelId) -> PathBuf {
+        saved_dir().join(format!("{channel_id}.surql"))
+    }
+}
+
+/// Writes the session settings and an export of its database to disk.
+#[instrument(skip(conn))]
+pub async fn save(channel_id: ChannelId, conn: &Conn) -> Result<(), anyhow::Error> {
+    tokio::fs::create_dir_all(saved_dir()).await?;
+
+    if !conn.engine.is_on_disk() {
+        replace(&SavedSession::export_path(channel_id), conn.export().await?).await?;
+    }
+
+    let saved = SavedSession::from_conn(channel_id, conn).await;
+    replace(
+        &SavedSession::meta_path(channel_id),
+        serde_json::to_vec_pretty(&saved)?,
+    )
+    .await?;
+    debug!("Session saved");
+    Ok(())
+}
+
+/// Writes a file through a temporary one, so a crash halfway through leaves the previous version intact.
+async fn replace(path: &Path, contents: Vec<u8>) -> Result<(), std::io::Error> {
+    let temporary = path.with_extension("tmp");
+    tokio::fs::write(&temporary, contents).await?;
+    tokio::fs::rename(&temporary, path).await
+}
+
+/// Periodically saves every session, so they come back after a crash and not only after a graceful shutdown.
+pub fn start() {
+    tokio::spawn(
+        async {
+            let mut ticks = interval(CHECKPOINT_INTERVAL);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            // The first tick completes immediately, sessions are only being restored then.
+            ticks.tick().await;
+            loop {
+                ticks.tick().await;
+                checkpoint_all().await;
+            }
+        }
+        .in_current_span(),
+    );
+}
+
+/// Saves every session. In-memory ones are always exported again, as a query can write
+/// in ways that can't be told from its text, and a stale export would lose that on a crash.
+async fn checkpoint_all() {
+    let conns: Vec<(ChannelId, Conn)> = DBCONNS
+        .lock()
+        .await
+        .iter()
+        .map(|(id, conn)| (ChannelId(*id), conn.clone()))
+        .collect();
+    for (channel_id, conn) in conns {
+        if let Err(err) = save(channel_id, &conn).await {
+            error!(error = %err, channel_id = %channel_id, "Failed to checkpoint session");
+            continue;
+        }
+        // Sessions cleaned up while being saved shouldn't come back on the next start.
+        if !DBCONNS.lock().await.contains_key(channel_id.as_u64()) {
+            remove(channel_id).await;
+        }
+    }
+}
+
+/// Forgets a saved session, so it isn't restored on the next start.
+pub async fn remove(channel_id: ChannelId) {
+    tokio::fs::remove_file(SavedSession::meta_path(channel_id))
+        .await
+        .ok();
+    tokio::fs::remove_file(SavedSession::export_path(channel_id))
+        .await
+        .ok();
+}
+
+/// Re-registers every session saved by a previous shutdown.
+/// Only runs once per process, as `ready` can fire again after a gateway reconnect.
+pub async fn restore_all(ctx: &Context) {
+    if RESTORED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let mut entries = match tokio::fs::read_dir(saved_dir()).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return,
+        Err(err) => {
+            error!(error = %err, "Failed to read saved sessions");
+            return;
+        }
+    };
+
+    let mut restored = 0;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if path.extension() != Some(OsStr::new("json")) {
+            continue;
+        }
+        let saved: SavedSession = match tokio::fs::read(&path)
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| serde_json::from_slice(&bytes).map_err(Into::into))
+        {
+            Ok(saved) => saved,
+            Err(err) => {
+                error!(error = %err, path = %path.display(), "Failed to read saved session");
+                continue;
+            }
+        };
+        let channel_id = saved.channel_id;
+        match restore(ctx, saved).await {
+            Ok(()) => {
+                restored += 1;
+                remove(channel_id).await;
+            }
+            Err(err) => {
+                error!(error = %err, channel_id = %channel_id, "Failed to restore session");
+            }
+        }
+    }
+    info!(restored, "Restored saved sessions");
+}
+
+#[instrument(skip(ctx, saved), fields(channel_id = %saved.channel_id))]
+async fn restore(ctx: &Context, saved: SavedSession) -> Result<(), anyhow::Error> {
+    let channel = match saved.channel_id.to_channel(ctx).await {
+        Ok(channel) => channel
+            .guild()
+            .ok_or(anyhow!("Saved session is not in a guild"))?,
+        Err(serenity::Error::Http(err))
+            if matches!(
+                *err,
+                HttpError::UnsuccessfulRequest(ErrorResponse {
+                    status_code: StatusCode::NOT_FOUND,
+                    ..
+                })
+            ) =>
+        {
+            // The channel is gone, there is nothing left to restore into.
+            warn!("Saved session channel no longer exists");
+            remove(saved.channel_id).await;
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let config = get_config(channel.guild_id)
+        .await?
+        .ok_or(anyhow!("No config for this server"))?;
+
+    let db = open_db_instance(&config, saved.engine, saved.channel_id).await?;
+    if !saved.engine.is_on_disk() {
+        db.root(&saved.location)
+            .import(SavedSession::export_path(saved.channel_id))
+            .await?;
+    }
+
+    let mut sessions = HashMap::new();
+    for (user, saved_identity) in &saved.identities {
+        match identity::session(&db, saved_identity).await {
+            Ok(session) => _ = sessions.insert(*user, session),
+            // Their queries are refused until they sign in again.
+            Err(err) => debug!(error = %err, user_id = %user, "Failed to restore identity"),
+        }
+    }
+
+    let conn = Conn {
+        db,
+        engine: saved.engine,
+        last_used: Instant::now()
+            .checked_sub(saved.idle)
+            .unwrap_or_else(Instant::now),
+        conn_type: saved.conn_type,
+        ttl: saved.ttl,
+        created: Instant::now()
+            .checked_sub(saved.age)
+            .unwrap_or_else(Instant::now),
+        extensions: saved.extensions,
+        ttl_warning: config.ttl_warning,
+        max_lifetime: config.max_lifetime,
+        queries: Arc::new(AtomicU64::new(saved.queries)),
+        pretty: saved.pretty,
+        json: saved.json,
+        require_query: saved.require_query,
+        versions: Arc::new(Mutex::new(saved.versions.into())),
+        checkpoints: saved.checkpoints,
+        undo: saved.undo,
+        undo_snapshot: Default::default(),
+        live_queries: Default::default(),
+        dashboards: Default::default(),
+        schedules: Default::default(),
+        location: Arc::new(Mutex::new(saved.location)),
+        params: Arc::new(Mutex::new(saved.params)),
+        quotas: config.quotas,
+        over_quota: Default::default(),
+        measurements: Default::default(),
+        identities: Arc::new(Mutex::new(saved.identities)),
+        sessions: Arc::new(Mutex::new(sessions)),
+    };
+    let conn = register_conn(ctx.clone(), conn, channel).await?;
+    for dashboard in saved.dashboards {
+        if !dashboards::start(ctx.http.clone(), saved.channel_id, &conn, dashboard).await {
+            warn!("Saved session has too many dashboards, dropping the rest");
+        }
+    }
+    for schedule in saved.schedules {
+        schedules::start(ctx.http.clone(), saved.channel_id, &conn, schedule).await;
+    }
+    debug!("Session restored");
+    Ok(())
+}
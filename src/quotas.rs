@@ -0,0 +1,216 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use surrealdb::sql;
+use tokio::time::{Duration, Instant};
+
+use crate::{location, session_db::Handle};
+
+const MB: u64 = 1024 * 1024;
+/// Sessions are measured at most this often, writes in between are measured once it's over.
+pub const MEASURE_INTERVAL: Duration = Duration::from_secs(10);
+/// How many records of each table are read to estimate how large the table is when exported.
+const SAMPLE_SIZE: u64 = 20;
+
+/// Limits on how much a single session can hold, so one user can't take the bot host down.
+/// A limit of 0 disables that quota.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct Quotas {
+    /// Largest file that can be loaded into a session, in bytes.
+    pub max_attachment_size: u64,
+    /// Largest a session's export can grow, in bytes.
+    pub max_export_size: u64,
+    /// Most records a session can hold, across all of its tables.
+    pub max_records: u64,
+    /// Most statements a single query can have.
+    pub max_statements: u64,
+}
+
+impl Default for Quotas {
+    fn default() -> Self {
+        Quotas {
+            max_attachment_size: 8 * MB,
+            max_export_size: 32 * MB,
+            max_records: 100_000,
+            max_statements: 100,
+        }
+    }
+}
+
+/// Which quota was hit, and by how much.
+#[derive(Debug, Clone)]
+pub enum Exceeded {
+    AttachmentSize { size: u64, max: u64 },
+    ExportSize { size: u64, max: u64 },
+    Records { count: u64, max: u64 },
+    Statements { count: u64, max: u64 },
+}
+
+impl fmt::Display for Exceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Exceeded::AttachmentSize { size, max } => write!(
+                f,
+                "The file is {}, over the {} limit for files loaded into a session.",
+                format_size(*size),
+                format_size(*max)
+            ),
+            Exceeded::ExportSize { size, max } => write!(
+                f,
+                "The session's data takes about {} when exported, over the {} limit.",
+                format_size(*size),
+                format_size(*max)
+            ),
+            Exceeded::Records { count, max } => write!(
+                f,
+                "The session holds {count} records, over the limit of {max}."
+            ),
+            Exceeded::Statements { count, max } => write!(
+                f,
+                "The query has {count} statements, over the limit of {max} per query."
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Exceeded {}
+
+impl Exceeded {
+    /// What the user can do about it.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            Exceeded::AttachmentSize { .. } => "Try loading a smaller file.",
+            Exceeded::ExportSize { .. } | Exceeded::Records { .. } => {
+                "Queries adding data are blocked until some is removed with `DELETE` or `REMOVE`."
+            }
+            Exceeded::Statements { .. } => "Try splitting the query into several smaller ones.",
+        }
+    }
+}
+
+pub fn format_size(bytes: u64) -> String {
+    if bytes < MB {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MiB", bytes as f64 / MB as f64)
+    }
+}
+
+fn format_limit(limit: u64, format: impl Fn(u64) -> String) -> String {
+    match limit {
+        0 => "unlimited".to_string(),
+        limit => format(limit),
+    }
+}
+
+fn check(limit: u64, value: u64) -> bool {
+    limit == 0 || value <= limit
+}
+
+impl Quotas {
+    pub fn check_attachment(&self, size: u64) -> Result<(), Exceeded> {
+        match check(self.max_attachment_size, size) {
+            true => Ok(()),
+            false => Err(Exceeded::AttachmentSize {
+                size,
+                max: self.max_attachment_size,
+            }),
+        }
+    }
+
+    /// Queries that don't parse are let through, so the database can explain what's wrong with them.
+    pub fn check_statements(&self, query: &str) -> Result<(), Exceeded> {
+        let Ok(parsed) = sql::parse(query) else {
+            return Ok(());
+        };
+        let count = parsed.len() as u64;
+        match check(self.max_statements, count) {
+            true => Ok(()),
+            false => Err(Exceeded::Statements {
+                count,
+                max: self.max_statements,
+            }),
+        }
+    }
+
+    pub fn check_records(&self, count: u64) -> Result<(), Exceeded> {
+        match check(self.max_records, count) {
+            true => Ok(()),
+            false => Err(Exceeded::Records {
+                count,
+                max: self.max_records,
+            }),
+        }
+    }
+
+    pub fn check_export(&self, size: u64) -> Result<(), Exceeded> {
+        match check(self.max_export_size, size) {
+            true => Ok(()),
+            false => Err(Exceeded::ExportSize {
+                size,
+                max: self.max_export_size,
+            }),
+        }
+    }
+
+    /// Summary for the server config panel.
+    pub fn describe(&self) -> String {
+        format!(
+            "Files loaded: {}\nExported data: {}\nRecords: {}\nStatements per query: {}",
+            format_limit(self.max_attachment_size, format_size),
+            format_limit(self.max_export_size, format_size),
+            format_limit(self.max_records, |n| n.to_string()),
+            format_limit(self.max_statements, |n| n.to_string()),
+        )
+    }
+}
+
+/// What a session holds, as last measured.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Usage {
+    pub records: u64,
+    /// Estimated size of the session's export, in bytes.
+    pub size: u64,
+}
+
+/// When a session was last measured, and whether a measurement is already waiting for its turn.
+#[derive(Debug, Default)]
+pub struct Measurements {
+    pub last: Option<Instant>,
+    pub pending: bool,
+}
+
+/// Counts the records in every table of every database, and estimates how large they are exported
+/// from a sample of each table rather than exporting them.
+pub async fn measure(db: &Handle) -> surrealdb::Result<Usage> {
+    let mut usage = Usage::default();
+    for namespace in location::namespaces(db).await? {
+        for database in location::databases(db, &namespace).await? {
+            for table in location::tables(db, &namespace, &database).await? {
+                let mut response = db
+                    .query(format!(
+                        "USE NS {ns} DB {db}; (SELECT count() FROM {table} GROUP ALL)[0].count; SELECT * FROM {table} LIMIT {SAMPLE_SIZE}",
+                        ns = sql::Ident::from(namespace.as_str()),
+                        db = sql::Ident::from(database.as_str()),
+                        table = sql::Ident::from(table.as_str()),
+                    ))
+                    .await?;
+                let count = match response.take(1)? {
+                    sql::Value::Number(n) => n.as_int() as u64,
+                    _ => 0,
+                };
+                let sample = match response.take(2)? {
+                    sql::Value::Array(records) => records.0,
+                    _ => Vec::new(),
+                };
+                usage.records += count;
+                if !sample.is_empty() {
+                    let sampled: u64 = sample.iter().map(|r| r.to_string().len() as u64).sum();
+                    usage.size += sampled * count / sample.len() as u64;
+                }
+            }
+        }
+    }
+    Ok(usage)
+}
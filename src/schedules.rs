@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use serenity::{
+    http::Http,
+    model::prelude::{ChannelId, UserId},
+};
+use tokio::{
+    task::AbortHandle,
+    time::{interval, Duration, Instant, MissedTickBehavior},
+};
+use tracing::Instrument;
+
+use crate::{has_writes, utils::system_message, Conn, DBCONNS};
+
+pub const MAX_SCHEDULES: usize = 5;
+pub const MIN_INTERVAL: Duration = Duration::from_secs(2);
+/// How long a schedule keeps its session alive, after which the session expires as usual unless someone uses it.
+pub const KEEP_ALIVE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A query run repeatedly in the background of a session.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Schedule {
+    pub id: u32,
+    pub interval: Duration,
+    pub query: String,
+    pub creator: UserId,
+}
+
+/// Adds a schedule with the next free id, unless the session already has [`MAX_SCHEDULES`].
+pub async fn add(
+    http: impl AsRef<Http> + Send + Sync + 'static,
+    channel: ChannelId,
+    conn: &Conn,
+    interval: Duration,
+    query: String,
+    creator: UserId,
+) -> Option<u32> {
+    let mut schedules = conn.schedules.lock().await;
+    if schedules.len() >= MAX_SCHEDULES {
+        return None;
+    }
+    let id = schedules.keys().next_back().map_or(1, |id| id + 1);
+    let schedule = Schedule {
+        id,
+        interval,
+        query,
+        creator,
+    };
+    let task = spawn(http, channel, conn, schedule.clone());
+    schedules.insert(id, (schedule, task));
+    Some(id)
+}
+
+/// Runs a schedule until it's removed, fails, or the session ends.
+pub async fn start(
+    http: impl AsRef<Http> + Send + Sync + 'static,
+    channel: ChannelId,
+    conn: &Conn,
+    schedule: Schedule,
+) {
+    let mut schedules = conn.schedules.lock().await;
+    let task = spawn(http, channel, conn, schedule.clone());
+    schedules.insert(schedule.id, (schedule, task));
+}
+
+/// Runs count as activity for [`KEEP_ALIVE`], so scheduled sessions don't expire while a demo is running,
+/// but don't keep a forgotten session around forever either.
+fn spawn(
+    http: impl AsRef<Http> + Send + Sync + 'static,
+    channel: ChannelId,
+    conn: &Conn,
+    schedule: Schedule,
+) -> AbortHandle {
+    let registry = conn.schedules.clone();
+    let started = Instant::now();
+    let task = tokio::spawn(
+        async move {
+            let mut ticks = interval(schedule.interval);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            // The first tick completes immediately, the query should first run after one interval.
+            ticks.tick().await;
+            loop {
+                ticks.tick().await;
+                let conn = match DBCONNS.lock().await.get_mut(channel.as_u64()) {
+                    Some(c) => {
+                        if started.elapsed() < KEEP_ALIVE {
+                            c.last_used = Instant::now();
+                        }
+                        c.clone()
+                    }
+                    None => return,
+                };
+                let result = run(&conn, &schedule).await;
+                if let Err(err) = result {
+                    registry.lock().await.remove(&schedule.id);
+                    system_message(
+                        http.as_ref(),
+                        &channel,
+                        "Scheduled query stopped",
+                        format!(
+                            "Schedule `{}` failed and has been removed:\n```rust\n{err}\n```",
+                            schedule.id
+                        ),
+                        Some(false),
+                        Some(format!("<@{}>", schedule.creator)),
+                        None,
+                    )
+                    .await
+                    .ok();
+                    return;
+                }
+            }
+        }
+        .in_current_span(),
+    );
+    task.abort_handle()
+}
+
+/// Runs a scheduled query once, as the identity its creator picked and within the session's quotas.
+async fn run(conn: &Conn, schedule: &Schedule) -> Result<(), anyhow::Error> {
+    conn.check_query(&schedule.query).await?;
+    let Ok(handle) = conn.handle(Some(schedule.creator)).await else {
+        anyhow::bail!("The identity its creator picked with `/auth` can't be used anymore");
+    };
+    handle.query(&schedule.query).await?.check()?;
+    if has_writes(&schedule.query) {
+        conn.check_usage().await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,420 @@
+// Results carry `surrealdb::Error`, like the SDK methods they stand in for.
+#![allow(clippy::result_large_err)]
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::Path,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, Weak,
+    },
+    task::{Context, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures::Stream;
+use serde::Serialize;
+use surrealdb::{
+    channel::{self, Receiver, Sender},
+    dbs::{Capabilities, Notification, QueryType, Response as StatementResult, Session},
+    error::Api,
+    kvs::{Datastore, LockType, TransactionType},
+    sql::{self, Value},
+    Error,
+};
+use tokio::{task::AbortHandle, time::Duration};
+
+use crate::{has_writes, identity::ROOT, location::Location};
+
+/// A session's datastore, shared by everyone querying it.
+/// Each of them gets a [`Handle`] with a session of their own, so nobody waits on anyone else's identity.
+#[derive(Clone)]
+pub struct SessionDb {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    kvs: Datastore,
+    /// Where the notifications of each running live query go.
+    live: Mutex<HashMap<sql::Uuid, Sender<Notification>>>,
+    dispatcher: AbortHandle,
+    /// Queries that may write which have started running on the datastore.
+    writes_started: AtomicU64,
+    /// Queries that may write which are done running, whether they failed or not.
+    writes_finished: AtomicU64,
+}
+
+/// How many queries that may write ran on a datastore, to tell whether anything wrote in between two points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Writes {
+    pub started: u64,
+    pub finished: u64,
+}
+
+impl Writes {
+    /// Whether no write was running when these were counted.
+    pub fn idle(&self) -> bool {
+        self.started == self.finished
+    }
+
+    /// Whether exactly one write started and finished since `before`.
+    pub fn one_since(&self, before: Writes) -> bool {
+        self.started == before.started + 1 && self.finished == before.finished + 1
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.dispatcher.abort();
+    }
+}
+
+impl fmt::Debug for SessionDb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionDb")
+            .field("handles", &Arc::strong_count(&self.inner))
+            .finish()
+    }
+}
+
+impl SessionDb {
+    /// Opens the datastore at `path`, like `memory` or `surrealkv://sessions/1234`,
+    /// with the bot's root user defined on it.
+    pub async fn open(
+        path: &str,
+        capabilities: Capabilities,
+        timeout: Option<Duration>,
+    ) -> Result<Self, Error> {
+        let kvs = Datastore::new(path)
+            .await?
+            .with_notifications()
+            .with_capabilities(capabilities)
+            .with_query_timeout(timeout)
+            .with_transaction_timeout(timeout)
+            .with_auth_enabled(true);
+        kvs.check_version().await?;
+        kvs.bootstrap().await?;
+        kvs.initialise_credentials(ROOT.username, ROOT.password)
+            .await?;
+        let notifications = kvs.notifications();
+        Ok(SessionDb {
+            inner: Arc::new_cyclic(|inner| Inner {
+                kvs,
+                live: Mutex::new(HashMap::new()),
+                dispatcher: tokio::spawn(dispatch(inner.clone(), notifications)).abort_handle(),
+                writes_started: AtomicU64::new(0),
+                writes_finished: AtomicU64::new(0),
+            }),
+        })
+    }
+
+    /// A handle running queries with `session`.
+    pub fn handle(&self, session: Session) -> Handle {
+        Handle {
+            db: self.clone(),
+            session: session.with_rt(true),
+            vars: BTreeMap::new(),
+        }
+    }
+
+    /// A handle for the bot's own work as the root user, in `location`.
+    pub fn root(&self, location: &Location) -> Handle {
+        self.handle(Session::owner())
+            .at(&location.namespace, &location.database)
+    }
+
+    /// How many clones of this datastore are alive, including handles, responses and live streams.
+    pub fn handles(&self) -> usize {
+        Arc::strong_count(&self.inner)
+    }
+
+    pub fn kvs(&self) -> &Datastore {
+        &self.inner.kvs
+    }
+
+    pub fn writes(&self) -> Writes {
+        // Finished is read first, so a write running in between always shows up as unfinished.
+        let finished = self.inner.writes_finished.load(Ordering::SeqCst);
+        Writes {
+            started: self.inner.writes_started.load(Ordering::SeqCst),
+            finished,
+        }
+    }
+
+    /// Counts `run` as a write, if `writes` is set.
+    async fn write<T>(&self, writes: bool, run: impl std::future::Future<Output = T>) -> T {
+        if !writes {
+            return run.await;
+        }
+        self.inner.writes_started.fetch_add(1, Ordering::SeqCst);
+        let result = run.await;
+        self.inner.writes_finished.fetch_add(1, Ordering::SeqCst);
+        result
+    }
+
+    /// Stops a live query in the background, as this is called when its stream is dropped.
+    fn kill(&self, id: sql::Uuid, session: &Session) {
+        self.inner.live.lock().unwrap().remove(&id);
+        let db = self.clone();
+        // KILL finds the live query by its id, it only needs a namespace and database to run in.
+        let mut owner = Session::owner().with_rt(true);
+        owner.ns.clone_from(&session.ns);
+        owner.db.clone_from(&session.db);
+        tokio::spawn(async move {
+            let vars = BTreeMap::from([("id".to_string(), Value::Uuid(id))]);
+            if let Err(err) = db.inner.kvs.execute("KILL $id", &owner, Some(vars)).await {
+                debug!(error = %err, "Failed to kill live query");
+            }
+        });
+    }
+}
+
+/// Whether the token a session signed in with has expired, queries would fail with it.
+pub fn expired(session: &Session) -> bool {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    session.exp.is_some_and(|exp| now > exp)
+}
+
+/// Hands notifications to the stream of the live query they belong to, until the datastore is closed.
+async fn dispatch(inner: Weak<Inner>, notifications: Option<Receiver<Notification>>) {
+    let Some(notifications) = notifications else {
+        return;
+    };
+    while let Ok(notification) = notifications.recv().await {
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        let sender = inner.live.lock().unwrap().get(&notification.id).cloned();
+        if let Some(sender) = sender {
+            // A closed channel means the stream was dropped, which kills the live query.
+            let _ = sender.send(notification).await;
+        }
+    }
+}
+
+/// Runs queries on a session datastore as one identity, in one namespace and database.
+#[derive(Clone, Debug)]
+pub struct Handle {
+    db: SessionDb,
+    session: Session,
+    /// Parameters bound on every query, set with `/param`.
+    vars: BTreeMap<String, Value>,
+}
+
+impl Handle {
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Binds parameters on every query run with this handle.
+    pub fn with_vars(mut self, vars: impl Serialize + 'static) -> Result<Self, Error> {
+        if let Value::Object(vars) = sql::to_value(vars)? {
+            self.vars.extend(vars.0);
+        }
+        Ok(self)
+    }
+
+    /// The same identity in another namespace and database.
+    pub fn at(mut self, namespace: &str, database: &str) -> Self {
+        self.session.ns = Some(namespace.to_string());
+        self.session.db = Some(database.to_string());
+        self
+    }
+
+    pub async fn query(&self, query: impl AsRef<str>) -> Result<Response, Error> {
+        self.query_with(query, ()).await
+    }
+
+    /// Runs a query with `vars` bound on top of the handle's own parameters.
+    pub async fn query_with(
+        &self,
+        query: impl AsRef<str>,
+        vars: impl Serialize + 'static,
+    ) -> Result<Response, Error> {
+        let mut all = self.vars.clone();
+        if let Value::Object(vars) = sql::to_value(vars)? {
+            all.extend(vars.0);
+        }
+        // Boxed, as the datastore's futures are too deeply nested to be laid out inside their callers.
+        let query = query.as_ref();
+        let results = Box::pin(self.db.write(
+            has_writes(query),
+            self.db.inner.kvs.execute(query, &self.session, Some(all)),
+        ))
+        .await?;
+        let live = results
+            .iter()
+            .enumerate()
+            .filter_map(|(index, response)| match response {
+                StatementResult {
+                    query_type: QueryType::Live,
+                    result: Ok(Value::Uuid(id)),
+                    ..
+                } => Some((index, *id)),
+                _ => None,
+            })
+            .collect();
+        Ok(Response {
+            db: self.db.clone(),
+            session: self.session.clone(),
+            results: results.into_iter().map(Some).collect(),
+            live,
+        })
+    }
+
+    /// Runs a SurrealQL file, failing on the first statement that fails.
+    pub async fn import(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let sql = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|error| Api::FileRead {
+                path: path.to_path_buf(),
+                error,
+            })?;
+        let imported = self
+            .db
+            .write(true, self.db.inner.kvs.import(&sql, &self.session))
+            .await?;
+        for response in imported {
+            response.result?;
+        }
+        Ok(())
+    }
+
+    /// The users and access methods defined on the root, or on `namespace`, as SurrealQL
+    /// overwriting any existing definition, without the bot's own root user.
+    /// Unlike with `INFO`, access keys aren't redacted, so they can be exported.
+    pub async fn auth_definitions(&self, namespace: Option<&str>) -> Result<Vec<String>, Error> {
+        let tx = self
+            .db
+            .inner
+            .kvs
+            .transaction(TransactionType::Read, LockType::Optimistic)
+            .await?;
+        let (users, accesses) = match namespace {
+            None => (tx.all_root_users().await, tx.all_root_accesses().await),
+            Some(ns) => (tx.all_ns_users(ns).await, tx.all_ns_accesses(ns).await),
+        };
+        tx.cancel().await?;
+        let mut definitions = Vec::new();
+        for user in users?.iter() {
+            if namespace.is_none() && user.name.0 == ROOT.username {
+                continue;
+            }
+            let mut user = user.clone();
+            user.if_not_exists = false;
+            user.overwrite = true;
+            definitions.push(format!("{user};"));
+        }
+        for access in accesses?.iter() {
+            let mut access = access.clone();
+            access.if_not_exists = false;
+            access.overwrite = true;
+            definitions.push(format!("{access};"));
+        }
+        Ok(definitions)
+    }
+
+    /// Exports the namespace and database of the handle as SurrealQL.
+    pub async fn export(&self) -> Result<Vec<u8>, Error> {
+        let (tx, rx) = channel::bounded(1);
+        let export = self.db.inner.kvs.export(&self.session, tx).await?;
+        let collect = async {
+            let mut acc = Vec::new();
+            while let Ok(bytes) = rx.recv().await {
+                acc.extend(bytes);
+            }
+            acc
+        };
+        let (result, acc) = tokio::join!(export, collect);
+        result?;
+        Ok(acc)
+    }
+}
+
+/// Results of a query, one per statement that produces one.
+/// Live queries that are not taken as a stream are killed when the response is dropped.
+pub struct Response {
+    db: SessionDb,
+    session: Session,
+    results: Vec<Option<StatementResult>>,
+    /// Live queries started by the query and not taken as a stream yet, by statement index.
+    live: HashMap<usize, sql::Uuid>,
+}
+
+impl Response {
+    pub fn num_statements(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Takes the result of the statement at `index`, `NONE` when there is none.
+    pub fn take(&mut self, index: usize) -> Result<Value, Error> {
+        match self.results.get_mut(index).and_then(Option::take) {
+            Some(response) => Ok(response.result?),
+            None => Ok(Value::None),
+        }
+    }
+
+    /// Fails with the first error of the query, if any of its statements failed.
+    pub fn check(mut self) -> Result<Self, Error> {
+        let failed = self
+            .results
+            .iter()
+            .position(|r| r.as_ref().is_some_and(|r| r.result.is_err()));
+        if let Some(index) = failed {
+            self.take(index)?;
+        }
+        Ok(self)
+    }
+
+    /// Takes the notifications of the live query started by the statement at `index`.
+    pub fn stream(&mut self, index: usize) -> Result<LiveStream, Error> {
+        let Some(id) = self.live.remove(&index) else {
+            return Err(Api::NotLiveQuery(index).into());
+        };
+        self.results[index] = None;
+        let (tx, rx) = channel::unbounded();
+        self.db.inner.live.lock().unwrap().insert(id, tx);
+        Ok(LiveStream {
+            db: self.db.clone(),
+            session: self.session.clone(),
+            id,
+            notifications: Box::pin(rx),
+        })
+    }
+}
+
+impl Drop for Response {
+    fn drop(&mut self) {
+        for (_, id) in self.live.drain() {
+            self.db.kill(id, &self.session);
+        }
+    }
+}
+
+/// Notifications of a live query, which is killed when the stream is dropped.
+pub struct LiveStream {
+    db: SessionDb,
+    session: Session,
+    id: sql::Uuid,
+    notifications: Pin<Box<Receiver<Notification>>>,
+}
+
+impl Stream for LiveStream {
+    type Item = Notification;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.notifications.as_mut().poll_next(cx)
+    }
+}
+
+impl Drop for LiveStream {
+    fn drop(&mut self) {
+        self.db.kill(self.id, &self.session);
+    }
+}
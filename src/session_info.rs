@@ -0,0 +1,116 @@
+use serenity::{builder::CreateEmbed, model::prelude::ChannelId};
+
+use crate::{
+    db_utils::get_channel_info,
+    location::{self, LOCATION_FIELD},
+    utils::unix_timestamp,
+    Conn,
+};
+
+/// Embed fields hold at most 1024 characters.
+const FIELD_LIMIT: usize = 1024;
+
+/// What a session holds and how long it has left, from `INFO` on its current namespace and database.
+#[derive(Debug, Default)]
+pub struct SessionInfo {
+    /// Name, record count and index names of every table.
+    pub tables: Vec<(String, u64, Vec<String>)>,
+    pub functions: Vec<String>,
+    pub params: Vec<String>,
+    pub accesses: Vec<String>,
+}
+
+impl SessionInfo {
+    pub async fn collect(conn: &Conn) -> Result<Self, surrealdb::Error> {
+        let current = conn.location().await;
+        let (ns, db) = (current.namespace.as_str(), current.database.as_str());
+        let root = conn.root().await;
+        let mut tables = Vec::new();
+        for table in location::tables(&root, ns, db).await? {
+            let count = location::count(&root, ns, db, &table).await?;
+            let indexes = location::indexes(&root, ns, db, &table).await?;
+            tables.push((table, count, indexes));
+        }
+        tables.sort();
+        Ok(SessionInfo {
+            tables,
+            functions: location::definitions(&root, ns, db, "functions").await?,
+            params: location::definitions(&root, ns, db, "params").await?,
+            accesses: location::definitions(&root, ns, db, "accesses").await?,
+        })
+    }
+}
+
+/// Keeps as many lines as fit in an embed field, saying how many were left out.
+fn field_value(lines: Vec<String>) -> String {
+    if lines.is_empty() {
+        return "none".to_string();
+    }
+    let total = lines.len();
+    let mut kept = Vec::new();
+    let mut len = 0;
+    for line in lines {
+        // Leaves room for the note about the lines left out.
+        if len + line.len() + 1 > FIELD_LIMIT - 32 {
+            break;
+        }
+        len += line.len() + 1;
+        kept.push(line);
+    }
+    if kept.len() < total {
+        kept.push(format!("...and {} more", total - kept.len()));
+    }
+    kept.join("\n")
+}
+
+fn names(names: Vec<String>) -> String {
+    field_value(names.into_iter().map(|n| format!("`{n}`")).collect())
+}
+
+/// Summary of the session in `channel`, for `/session` and the session panel.
+pub async fn embed(conn: &Conn, channel: ChannelId) -> Result<CreateEmbed, surrealdb::Error> {
+    let info = SessionInfo::collect(conn).await?;
+    let started = match get_channel_info(channel).await {
+        Ok(Some(channel_info)) => format!(
+            "<t:{}:R> by <@{}>",
+            channel_info.created, channel_info.creator
+        ),
+        _ => format!("<t:{}:R>", unix_timestamp(conn.created)),
+    };
+    let expires = unix_timestamp(conn.expires_at());
+
+    let tables = info
+        .tables
+        .into_iter()
+        .map(|(table, count, indexes)| {
+            let records = match count {
+                1 => "1 record".to_string(),
+                n => format!("{n} records"),
+            };
+            match indexes.is_empty() {
+                true => format!("`{table}` {records}"),
+                false => format!(
+                    "`{table}` {records}, indexes {}",
+                    indexes
+                        .iter()
+                        .map(|i| format!("`{i}`"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            }
+        })
+        .collect();
+
+    let mut embed = CreateEmbed::default();
+    embed
+        .title("Session info")
+        .field(LOCATION_FIELD, conn.location().await.to_string(), false)
+        .field("Started", started, true)
+        .field("Expires", format!("<t:{expires}:R>"), true)
+        .field("Queries run", conn.queries(), true)
+        .field("Tables", field_value(tables), false)
+        .field("Functions", names(info.functions), false)
+        .field("Parameters", names(info.params), false)
+        .field("Access methods", names(info.accesses), false);
+    Ok(embed)
+}
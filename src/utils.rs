@@ -1,5 +1,6 @@
+use memorable_wordlist::kebab_case;
 use serenity::{
-    builder::{CreateInteractionResponse, EditInteractionResponse},
+    builder::{CreateInteractionResponse, CreateMessage, EditInteractionResponse},
     http::Http,
     json::{self, Value},
     model::{
@@ -8,11 +9,11 @@ use serenity::{
                 ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue,
             },
             command::CommandOptionType,
-            component::ButtonStyle::Primary,
+            component::ButtonStyle::{self, Primary},
             message_component::MessageComponentInteraction,
             modal::ModalSubmitInteraction,
-            AttachmentType, ChannelId, GuildChannel, InteractionId, Message, PermissionOverwrite,
-            PermissionOverwriteType,
+            ApplicationId, AttachmentType, ChannelId, ChannelType, Guild, GuildChannel, GuildId,
+            InteractionId, Message, PermissionOverwrite, PermissionOverwriteType, UserId,
         },
         user::User,
         Permissions,
@@ -22,18 +23,28 @@ use serenity::{
 use std::{
     borrow::Cow,
     cmp::Ordering,
-    sync::{Arc, LazyLock},
+    sync::{Arc, LazyLock, Mutex as StdMutex},
+    time::{SystemTime, UNIX_EPOCH},
 };
-use surrealdb::{
-    engine::local::{Db, Mem},
-    Surreal,
+use tokio::{
+    sync::Mutex,
+    time::{sleep, sleep_until, Duration, Instant},
 };
-use tokio::time::{sleep_until, Instant};
 use tracing::Instrument;
 
-use crate::{config::Config, db_utils::get_config, Conn, ConnType, DBCONNS};
+use crate::{
+    channel_info, checkpoints,
+    config::{Config, Engine},
+    db_utils::get_config,
+    persistence::{self, engine_dir},
+    quotas::Exceeded,
+    session_db::{Handle, SessionDb},
+    Conn, ConnType, DBCONNS,
+};
 
 pub const MAX_FILE_SIZE: usize = 24_000_000;
+/// How long cleaning up waits for queries and background tasks to let go of a database instance.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(60);
 
 pub enum CmdError {
     NoSubCommand,
@@ -60,6 +71,38 @@ pub enum CmdError {
     RegisterDB(anyhow::Error),
     UnsupportedChannelConnect,
     Stats(String),
+    InvalidCheckpointName(String),
+    UnknownCheckpoint(String),
+    TooManyCheckpoints(usize),
+    CheckpointFailed(anyhow::Error),
+    RollbackFailed(anyhow::Error),
+    UndoUnavailable,
+    NotQueryAuthor,
+    UndoFailed(anyhow::Error),
+    ForkFailed(anyhow::Error),
+    TooManyDashboards(usize),
+    WritingDashboard,
+    TooManySchedules(usize),
+    UnknownSchedule(u32),
+    IdentityExpired,
+    NoSessionIn(ChannelId),
+    DiffFailed(anyhow::Error),
+    InvalidParamName(String),
+    UnknownParam(String),
+    TooManyParams(usize),
+    UseFailed(anyhow::Error),
+    InvalidCapabilities(anyhow::Error),
+    QuotaExceeded(Exceeded),
+    TooManySessions(u64),
+    TooManyGuildSessions(u64),
+    NotSessionOwner(UserId),
+    NoPastSession,
+    ListSessions(surrealdb::Error),
+    NotBotOwner,
+    NoMaintenance,
+    SessionInfo(surrealdb::Error),
+    History(surrealdb::Error),
+    NoHistoryEntry,
 }
 
 impl CmdError {
@@ -160,6 +203,134 @@ impl CmdError {
                 "Please use /create or switch to a thread or SurrealQL channel".into()
             ),
             CmdError::Stats(e) => ("Statistics generation failed".into(), format!("Got error: \n{e}").into()),
+            CmdError::InvalidCheckpointName(name) => (
+                "Invalid checkpoint name".into(),
+                format!("`{name}` is not a valid checkpoint name.\nCheckpoint names can be up to {} characters long and may only contain letters, numbers, `-` and `_`.", checkpoints::MAX_NAME_LENGTH).into(),
+            ),
+            CmdError::UnknownCheckpoint(name) => (
+                "Unknown checkpoint".into(),
+                format!("There is no checkpoint called `{name}` in this session.\nYou can find the list of checkpoints on the session panel.").into(),
+            ),
+            CmdError::TooManyCheckpoints(max) => (
+                "Too many checkpoints".into(),
+                format!("Sessions can have at most {max} checkpoints.\nYou can overwrite an existing checkpoint by reusing its name.").into(),
+            ),
+            CmdError::CheckpointFailed(e) => (
+                "Checkpoint failed".into(),
+                format!("There was an error while creating the checkpoint:\n```rust\n{e}\n```").into(),
+            ),
+            CmdError::RollbackFailed(e) => (
+                "Rollback failed".into(),
+                format!("There was an error while rolling back the session:\n```rust\n{e}\n```").into(),
+            ),
+            CmdError::UndoUnavailable => (
+                "Can't undo this query".into(),
+                "Only the most recent query that changed data can be undone, and only once.".into(),
+            ),
+            CmdError::NotQueryAuthor => (
+                "Can't undo this query".into(),
+                "Only the person who sent a query can undo it.".into(),
+            ),
+            CmdError::UndoFailed(e) => (
+                "Undo failed".into(),
+                format!("There was an error while undoing the query:\n```rust\n{e}\n```").into(),
+            ),
+            CmdError::ForkFailed(e) => (
+                "Fork failed".into(),
+                format!("There was an error while forking the session:\n```rust\n{e}\n```").into(),
+            ),
+            CmdError::TooManyDashboards(max) => (
+                "Too many dashboards".into(),
+                format!("Sessions can have at most {max} dashboards.\nRemove one with its button before adding another.").into(),
+            ),
+            CmdError::WritingDashboard => (
+                "Dashboards can't write".into(),
+                "Dashboards are refreshed over and over, so their query can only read data, try `/schedule` to run a query that writes on an interval.".into(),
+            ),
+            CmdError::TooManySchedules(max) => (
+                "Too many schedules".into(),
+                format!("Sessions can have at most {max} scheduled queries.\nRemove one with `/schedule remove` before adding another.").into(),
+            ),
+            CmdError::UnknownSchedule(id) => (
+                "Unknown schedule".into(),
+                format!("There is no scheduled query with id `{id}` in this session.\nYou can find the list of schedules with `/schedule list`.").into(),
+            ),
+            CmdError::IdentityExpired => (
+                "Identity expired".into(),
+                "The identity you picked with `/auth` can't be used anymore, most likely because its token expired, so your query was not run.\nSign in again with `/auth`, or go back to the root user with `/auth reset`.".into(),
+            ),
+            CmdError::InvalidParamName(name) => (
+                "Invalid parameter name".into(),
+                format!("`{name}` is not a valid parameter name.\nParameter names may only contain letters, numbers and `_`, and can't be one of the Discord parameters bound on every query.").into(),
+            ),
+            CmdError::UnknownParam(name) => (
+                "Unknown parameter".into(),
+                format!("There is no parameter called `${name}` in this session.\nYou can find the list of parameters with `/param list`.").into(),
+            ),
+            CmdError::TooManyParams(max) => (
+                "Too many parameters".into(),
+                format!("Sessions can have at most {max} parameters.\nRemove one with `/param unset` before adding another.").into(),
+            ),
+            CmdError::UseFailed(e) => (
+                "Failed to switch namespace and database".into(),
+                format!("There was an error while switching:\n```rust\n{e}\n```").into(),
+            ),
+            CmdError::InvalidCapabilities(e) => (
+                "Invalid capabilities".into(),
+                format!("Function targets look like `string::*` or `http::get`, network targets like `surrealdb.com` or `10.0.0.0/8`:\n```rust\n{e}\n```").into(),
+            ),
+            CmdError::QuotaExceeded(exceeded) => (
+                "Quota exceeded".into(),
+                format!("{exceeded}\n{}", exceeded.hint()).into(),
+            ),
+            CmdError::TooManySessions(max) => (
+                "Too many sessions".into(),
+                format!("You can have at most {max} sessions running at once in this server.\nEnd one with its \"Stop session\" button before starting another.").into(),
+            ),
+            CmdError::TooManyGuildSessions(max) => (
+                "Too many sessions".into(),
+                format!("This server can have at most {max} sessions running at once.\nTry again once some have expired.").into(),
+            ),
+            CmdError::NotSessionOwner(creator) => (
+                "Not your session".into(),
+                format!("Only <@{creator}>, who started this session, or someone who can manage channels can stop it.").into(),
+            ),
+            CmdError::NoPastSession => (
+                "Nothing to reconnect".into(),
+                "This channel never had a session in this server, use `/connect` or `/create` to start one.".into(),
+            ),
+            CmdError::ListSessions(e) => (
+                "Failed to list sessions".into(),
+                format!("There was an error while reading the session registry:\n```rust\n{e}\n```").into(),
+            ),
+            CmdError::NotBotOwner => (
+                "Not allowed".into(),
+                "Only the owners of the bot can act on the sessions of every server.".into(),
+            ),
+            CmdError::NoMaintenance => (
+                "No maintenance planned".into(),
+                "There is no maintenance window to cancel, plan one with `/maintenance at`.".into(),
+            ),
+            CmdError::SessionInfo(e) => (
+                "Failed to read session info".into(),
+                format!("There was an error while looking into the session:\n```rust\n{e}\n```").into(),
+            ),
+            CmdError::History(e) => (
+                "Failed to read query history".into(),
+                format!("There was an error while reading the query history:\n```rust\n{e}\n```").into(),
+            ),
+            CmdError::NoHistoryEntry => (
+                "Query not found".into(),
+                "This query is no longer in the history of this channel.".into(),
+            ),
+            CmdError::NoSessionIn(channel) => (
+                "No session found".into(),
+                format!("There is no database instance in <#{channel}> that you can access.").into(),
+            ),
+            CmdError::DiffFailed(e) => (
+                "Diff failed".into(),
+                format!("There was an error while comparing sessions:\n```rust\n{e}\n```").into(),
+            ),
         }
     }
 
@@ -180,6 +351,16 @@ impl CmdError {
         let (title, description) = self.message();
         ephemeral_interaction_edit(http, interaction, title, description, Some(false)).await
     }
+
+    /// For errors outside of an interaction, like queries sent as messages.
+    pub async fn send(
+        &self,
+        http: impl AsRef<Http>,
+        channel: &ChannelId,
+    ) -> Result<(), anyhow::Error> {
+        let (title, description) = self.message();
+        system_message(http, channel, title, description, Some(false), None, None).await
+    }
 }
 
 /// ToInteraction is a trait that allows for easy conversion of different interaction types to a tuple of the interaction id and token.
@@ -369,8 +550,14 @@ pub fn read_view_perms(kind: PermissionOverwriteType) -> PermissionOverwrite {
 pub async fn clean_channel(mut channel: GuildChannel, http: impl AsRef<Http>) {
     info!("Cleaning up channel");
     let entry = DBCONNS.lock().await.remove(channel.id.as_u64());
+    if let Err(err) = channel_info::record_archived(channel.id).await {
+        error!(error = %err, "Failed to record archived session");
+    }
 
     if let Some(conn) = entry {
+        conn.kill_live_queries().await;
+        conn.stop_dashboards().await;
+        conn.stop_schedules().await;
         match system_message(
             &http,
             &channel.id,
@@ -428,6 +615,12 @@ pub async fn clean_channel(mut channel: GuildChannel, http: impl AsRef<Http>) {
                 }
             }
         }
+
+        let (db, engine) = (conn.db.clone(), conn.engine);
+        drop(conn);
+        persistence::remove(channel.id).await;
+        close_db_instance(db, engine, channel.id).await;
+        checkpoints::remove_all(channel.id).await;
     }
 
     let result = get_config(channel.guild_id).await;
@@ -457,25 +650,199 @@ pub async fn clean_channel(mut channel: GuildChannel, http: impl AsRef<Http>) {
         .ok();
 }
 
+/// Creates a private channel in the active category, visible only to the bot and `user`.
+pub async fn create_ephemeral_channel(
+    ctx: &Context,
+    guild_id: GuildId,
+    config: &Config,
+    application_id: ApplicationId,
+    user: UserId,
+) -> Result<GuildChannel, anyhow::Error> {
+    let guild = Guild::get(ctx, guild_id).await?;
+
+    let everyone = guild
+        .role_by_name("@everyone")
+        .ok_or(anyhow::anyhow!("No @everyone role in this server"))?;
+
+    let perms = vec![
+        PermissionOverwrite {
+            allow: Permissions::empty(),
+            deny: Permissions::VIEW_CHANNEL,
+            kind: PermissionOverwriteType::Role(everyone.id),
+        },
+        PermissionOverwrite {
+            allow: Permissions::VIEW_CHANNEL
+                .union(Permissions::SEND_MESSAGES)
+                .union(Permissions::READ_MESSAGE_HISTORY),
+            deny: Permissions::empty(),
+            kind: PermissionOverwriteType::Member(UserId(*application_id.as_u64())),
+        },
+        PermissionOverwrite {
+            allow: Permissions::VIEW_CHANNEL
+                .union(Permissions::SEND_MESSAGES)
+                .union(Permissions::READ_MESSAGE_HISTORY),
+            deny: Permissions::empty(),
+            kind: PermissionOverwriteType::Member(user),
+        },
+    ];
+
+    Ok(guild
+        .create_channel(ctx, |c| {
+            c.name(kebab_case(40))
+                .kind(ChannelType::Text)
+                .category(config.active_channel)
+                .permissions(perms)
+        })
+        .await?)
+}
+
+/// Sessions being started, counted against the limits until they're registered.
+static STARTING: StdMutex<Vec<(GuildId, UserId)>> = StdMutex::new(Vec::new());
+/// Held while checking the limits, so sessions started at the same time can't all fit in the last place.
+static MAKING_ROOM: Mutex<()> = Mutex::const_new(());
+
+/// A place taken by a session being started, until it's registered or the start is abandoned.
+#[must_use = "the place is released when dropped"]
+pub struct Room {
+    guild_id: GuildId,
+    user: UserId,
+}
+
+impl Room {
+    fn take(guild_id: GuildId, user: UserId) -> Self {
+        STARTING.lock().unwrap().push((guild_id, user));
+        Room { guild_id, user }
+    }
+}
+
+impl Drop for Room {
+    fn drop(&mut self) {
+        let mut starting = STARTING.lock().unwrap();
+        if let Some(i) = starting
+            .iter()
+            .position(|s| *s == (self.guild_id, self.user))
+        {
+            starting.swap_remove(i);
+        }
+    }
+}
+
+/// Makes sure `user` can start a new session in `guild_id` without going over the server's limits,
+/// archiving their oldest session if the server is configured to.
+/// The returned [`Room`] has to be kept until the session is registered.
+pub async fn make_room(
+    http: impl AsRef<Http>,
+    guild_id: GuildId,
+    user: UserId,
+    config: &Config,
+) -> Result<Room, CmdError> {
+    let _making_room = MAKING_ROOM.lock().await;
+    let limits = config.session_limits;
+    let active = match channel_info::active(guild_id).await {
+        Ok(active) => active,
+        Err(err) => {
+            // The registry is only used for limits, it shouldn't stop anyone from working.
+            error!(error = %err, "Failed to list active sessions");
+            return Ok(Room::take(guild_id, user));
+        }
+    };
+    let own: Vec<_> = active.iter().filter(|i| i.creator == user).collect();
+    let (starting, starting_own) = {
+        let starting = STARTING.lock().unwrap();
+        let in_guild = starting.iter().filter(|(g, _)| *g == guild_id);
+        (
+            in_guild.clone().count() as u64,
+            in_guild.filter(|(_, u)| *u == user).count() as u64,
+        )
+    };
+
+    let error = if limits.per_user != 0 && own.len() as u64 + starting_own >= limits.per_user {
+        CmdError::TooManySessions(limits.per_user)
+    } else if limits.per_guild != 0 && active.len() as u64 + starting >= limits.per_guild {
+        CmdError::TooManyGuildSessions(limits.per_guild)
+    } else {
+        return Ok(Room::take(guild_id, user));
+    };
+
+    let oldest = match own.first() {
+        Some(oldest) if limits.archive_oldest => oldest.channel_id,
+        _ => return Err(error),
+    };
+    let channel = match oldest.to_channel(http.as_ref()).await.map(|c| c.guild()) {
+        Ok(Some(channel)) => channel,
+        _ => return Err(error),
+    };
+    info!(channel_id = %oldest, "Archiving oldest session to make room");
+    system_message(
+        &http,
+        &oldest,
+        "Session archived",
+        format!("<@{user}> started a new session and was at the server's limit, so this one, their oldest, has been archived."),
+        None,
+        None,
+        None,
+    )
+    .await
+    .ok();
+    clean_channel(channel, http).await;
+    Ok(Room::take(guild_id, user))
+}
+
 #[instrument(skip_all)]
+#[allow(clippy::too_many_arguments)]
 pub async fn register_db(
     ctx: Context,
-    db: Surreal<Db>,
+    db: SessionDb,
+    engine: Engine,
     channel: GuildChannel,
     config: Config,
     conn_type: ConnType,
     require_query: bool,
+    creator: UserId,
 ) -> Result<Conn, anyhow::Error> {
     info!("Registering a new database");
+    if let Err(err) = channel_info::record_active(channel.id, channel.guild_id, creator).await {
+        error!(error = %err, "Failed to record session");
+    }
     let conn = crate::Conn {
         db,
+        engine,
         last_used: Instant::now(),
         conn_type,
         ttl: config.ttl,
+        created: Instant::now(),
+        extensions: 0,
+        ttl_warning: config.ttl_warning,
+        max_lifetime: config.max_lifetime,
+        queries: Default::default(),
         pretty: config.pretty,
         json: config.json,
         require_query,
+        versions: Default::default(),
+        checkpoints: Vec::new(),
+        undo: false,
+        undo_snapshot: Default::default(),
+        live_queries: Default::default(),
+        dashboards: Default::default(),
+        schedules: Default::default(),
+        location: Default::default(),
+        params: Default::default(),
+        quotas: config.quotas,
+        over_quota: Default::default(),
+        measurements: Default::default(),
+        identities: Default::default(),
+        sessions: Default::default(),
     };
+    register_conn(ctx, conn, channel).await
+}
+
+/// Associates an already built `Conn` with a channel and starts its TTL watchdog.
+#[instrument(skip_all)]
+pub async fn register_conn(
+    ctx: Context,
+    conn: Conn,
+    channel: GuildChannel,
+) -> Result<Conn, anyhow::Error> {
     DBCONNS
         .lock()
         .await
@@ -483,23 +850,32 @@ pub async fn register_db(
 
     tokio::spawn(
         async move {
-            let mut last_time;
-            let mut ttl;
+            // The expiry the channel was last warned about, using the session pushes it back.
+            let mut warned = None;
             loop {
-                match DBCONNS.lock().await.get(channel.id.as_u64()) {
-                    Some(e) => {
-                        last_time = e.last_used;
-                        ttl = e.ttl
-                    }
+                let (expires_at, ttl_warning) = match DBCONNS.lock().await.get(channel.id.as_u64())
+                {
+                    Some(e) => (e.expires_at(), e.ttl_warning),
                     None => {
                         break;
                     }
-                }
-                if last_time.elapsed() >= ttl {
+                };
+                let now = Instant::now();
+                if now >= expires_at {
                     clean_channel(channel, ctx.clone()).await;
                     break;
                 }
-                sleep_until(last_time + ttl).await;
+                let warn_at = expires_at.checked_sub(ttl_warning).unwrap_or(now);
+                if ttl_warning.is_zero() || warned == Some(expires_at) {
+                    sleep_until(expires_at).await;
+                } else if now >= warn_at {
+                    warned = Some(expires_at);
+                    if let Err(err) = expiry_warning(&ctx, channel.id, expires_at).await {
+                        error!(error = %err, "Failed to send expiry warning");
+                    }
+                } else {
+                    sleep_until(warn_at).await;
+                }
             }
         }
         .in_current_span(),
@@ -507,48 +883,122 @@ pub async fn register_db(
     Ok(conn)
 }
 
+/// Converts a point in time to a unix timestamp in seconds, for Discord's `<t:...>` markdown.
+pub fn unix_timestamp(at: Instant) -> u64 {
+    let at = SystemTime::now() + at.saturating_duration_since(Instant::now());
+    at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Warns a channel its session is about to expire, with buttons to extend or export it.
+async fn expiry_warning(
+    http: impl AsRef<Http>,
+    channel: ChannelId,
+    expires_at: Instant,
+) -> Result<(), anyhow::Error> {
+    let at_max_lifetime = DBCONNS
+        .lock()
+        .await
+        .get(channel.as_u64())
+        .is_some_and(|c| c.at_max_lifetime());
+    let expires = unix_timestamp(expires_at);
+    channel
+        .send_message(http.as_ref(), |m| {
+            m.embed(|e| {
+                e.title("Session expiring soon")
+                    .description(match at_max_lifetime {
+                        true => format!("This session expires <t:{expires}:R> and has reached the longest this server lets sessions live, so it can't be extended.\nExport it now to keep your data."),
+                        false => format!("This session expires <t:{expires}:R> unless it's used or extended.\nIts export will be posted here when it does."),
+                    })
+                    .color(0xffa500)
+            })
+            .components(|c| {
+                c.create_action_row(|r| {
+                    if !at_max_lifetime {
+                        r.create_button(|b| {
+                            b.custom_id("configurable_session:extend")
+                                .label("Extend")
+                                .style(Primary)
+                                .emoji('⏳')
+                        });
+                    }
+                    r.create_button(|b| {
+                        b.custom_id("configurable_session:export")
+                            .label("Export now")
+                            .style(ButtonStyle::Success)
+                            .emoji('📃')
+                    })
+                })
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+/// Wraps text in an attachment, truncating it to fit Discord's upload limit.
+/// Also returns whether the text had to be truncated.
+pub fn text_attachment(text: &str, filename: String) -> (AttachmentType<'_>, bool) {
+    let (data, truncated) = match text.len().cmp(&MAX_FILE_SIZE) {
+        Ordering::Equal | Ordering::Less => (text.as_bytes(), false),
+        Ordering::Greater => (text.as_bytes().split_at(MAX_FILE_SIZE).0, true),
+    };
+    (
+        AttachmentType::Bytes {
+            data: Cow::Borrowed(data),
+            filename,
+        },
+        truncated,
+    )
+}
+
+/// Replies to a query sent as a message, with an Undo button when `undoable` is set.
 pub async fn respond(
     reply: String,
     ctx: Context,
     query_msg: Message,
     conn: &Conn,
     channel_id: ChannelId,
+    undoable: bool,
 ) -> Result<(), anyhow::Error> {
+    let undo_button = |m: &mut CreateMessage| {
+        if undoable {
+            m.components(|c| {
+                c.create_action_row(|r| {
+                    r.create_button(|b| {
+                        b.custom_id(format!("configurable_session:undo:{}", query_msg.id))
+                            .label("Undo")
+                            .style(ButtonStyle::Secondary)
+                            .emoji('↩')
+                    })
+                })
+            });
+        }
+    };
     if reply.len() < 1900 {
-        query_msg
-            .reply(
-                &ctx,
-                format!(
+        channel_id
+            .send_message(&ctx, |m| {
+                m.reference_message(&query_msg).content(format!(
                     "```{}\n{}\n```",
                     if conn.json { "json" } else { "sql" },
                     reply
-                ),
-            )
+                ));
+                undo_button(m);
+                m
+            })
             .await
             .unwrap();
     } else {
-        let mut truncated = false;
-        let data = match reply.as_bytes().len().cmp(&MAX_FILE_SIZE) {
-            Ordering::Equal | Ordering::Less => reply.as_bytes(),
-            Ordering::Greater => {
-                truncated = true;
-                reply.as_bytes().split_at(MAX_FILE_SIZE).0
-            }
-        };
-        let reply_attachment = AttachmentType::Bytes {
-            data: std::borrow::Cow::Borrowed(data),
-            filename: format!("response.{}", if conn.json { "json" } else { "sql" }),
-        };
+        let (reply_attachment, truncated) = text_attachment(
+            &reply,
+            format!("response.{}", if conn.json { "json" } else { "sql" }),
+        );
         channel_id
             .send_message(&ctx, |m| {
-                let message = m.reference_message(&query_msg).add_file(reply_attachment);
+                m.reference_message(&query_msg).add_file(reply_attachment);
                 if truncated {
-                    message.content(
-                        ":information_source: Response was too long and has been truncated",
-                    )
-                } else {
-                    message
+                    m.content(":information_source: Response was too long and has been truncated");
                 }
+                undo_button(m);
+                m
             })
             .await
             .unwrap();
@@ -560,10 +1010,14 @@ pub async fn load_attachment(
     op_option: CommandDataOption,
     command: &ApplicationCommandInteraction,
     ctx: Context,
-    db: Surreal<Db>,
+    db: Handle,
     channel: GuildChannel,
 ) -> Result<(), anyhow::Error> {
     if let Some(CommandDataOptionValue::Attachment(attachment)) = op_option.resolved {
+        let conn = DBCONNS.lock().await.get(channel.id.as_u64()).cloned();
+        if let Some(Err(exceeded)) = conn.as_ref().map(|c| c.check_attachment(&attachment)) {
+            return CmdError::QuotaExceeded(exceeded).reply(&ctx, command).await;
+        }
         ephemeral_interaction(
             &ctx,
             command,
@@ -582,12 +1036,20 @@ pub async fn load_attachment(
                 let (_channel, ctx, command) = (channel.clone(), ctx.clone(), command.clone());
                 tokio::spawn(
                     async move {
-                        if let Err(why) =
-                            db.query(String::from_utf8_lossy(&data).into_owned()).await
-                        {
+                        if let Err(why) = db.query(String::from_utf8_lossy(&data)).await {
                             CmdError::BadQuery(why).edit(&ctx, &command).await.unwrap();
                             return;
                         }
+                        if let Some(Err(exceeded)) = match &conn {
+                            Some(conn) => Some(conn.check_usage().await),
+                            None => None,
+                        } {
+                            CmdError::QuotaExceeded(exceeded)
+                                .edit(&ctx, &command)
+                                .await
+                                .unwrap();
+                            return;
+                        }
                         ephemeral_interaction_edit(
                             &ctx,
                             &command,
@@ -620,17 +1082,71 @@ pub async fn load_attachment(
     }
 }
 
+/// Creates a fresh database instance for a channel, discarding any data
+/// an on-disk engine may have left behind from a previous session.
 #[instrument]
-pub async fn create_db_instance(server_config: &Config) -> Result<Surreal<Db>, anyhow::Error> {
+pub async fn create_db_instance(
+    server_config: &Config,
+    engine: Engine,
+    channel_id: ChannelId,
+) -> Result<SessionDb, anyhow::Error> {
     info!("Creating database instance");
-    let db_config = surrealdb::opt::Config::new()
-        .query_timeout(server_config.timeout)
-        .transaction_timeout(server_config.timeout);
-    let db = Surreal::new::<Mem>(db_config).await?;
+    if engine.is_on_disk() {
+        remove_engine_dir(channel_id).await;
+    }
+    open_db_instance(server_config, engine, channel_id).await
+}
+
+/// Opens a database instance for a channel, reusing existing on-disk data if there is any.
+#[instrument]
+pub async fn open_db_instance(
+    server_config: &Config,
+    engine: Engine,
+    channel_id: ChannelId,
+) -> Result<SessionDb, anyhow::Error> {
+    let path = match engine {
+        Engine::Memory => "memory".to_string(),
+        Engine::RocksDb => format!("rocksdb://{}", engine_dir(channel_id).display()),
+        Engine::SurrealKv => format!("surrealkv://{}", engine_dir(channel_id).display()),
+        Engine::SurrealKvVersioned => {
+            format!("surrealkv+versioned://{}", engine_dir(channel_id).display())
+        }
+    };
+    Ok(SessionDb::open(
+        &path,
+        server_config.capabilities.build()?,
+        Some(server_config.timeout),
+    )
+    .await?)
+}
 
-    db.use_ns("test").use_db("test").await?;
+/// Closes a channel's database instance, removing the files of on-disk engines
+/// once nothing uses it anymore, as the engine keeps them open until its last handle is dropped.
+pub async fn close_db_instance(db: SessionDb, engine: Engine, channel_id: ChannelId) {
+    if !engine.is_on_disk() {
+        return;
+    }
+    let deadline = Instant::now() + CLOSE_TIMEOUT;
+    while db.handles() > 1 {
+        if Instant::now() >= deadline {
+            warn!(channel_id = %channel_id, handles = db.handles(), "Database instance still in use, leaving its files behind");
+            return;
+        }
+        sleep(Duration::from_millis(500)).await;
+    }
+    drop(db);
+    remove_engine_dir(channel_id).await;
+}
 
-    Ok(db)
+async fn remove_engine_dir(channel_id: ChannelId) {
+    let path = engine_dir(channel_id);
+    match tokio::fs::remove_dir_all(&path).await {
+        Ok(()) => debug!(path = %path.display(), "Removed on-disk database"),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => {
+            error!(error = %err, path = %path.display(), "Failed to remove on-disk database")
+        }
+    }
 }
 
 static LOCK_FILE: &str = include_str!("../Cargo.lock");